@@ -3,9 +3,15 @@ use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main, Through
 use cc_garbling::circuit::Circuit;
 use cc_garbling::garbled_circuit::{GarbledCircuit, GarbledEncoder};
 use cc_garbling::garbled_circuit::classic::Classic;
+use cc_garbling::garbled_circuit::classic_free_xor::ClassicFreeXor;
 use cc_garbling::garbled_circuit::half_gates::HalfGates;
+use cc_garbling::garbled_circuit::three_halves::ThreeHalves;
+use cc_garbling::garbled_circuit::point_and_permute::PointAndPermute;
+use cc_garbling::garbled_circuit::grr3::Grr3;
+use cc_garbling::garbled_circuit::grr2::Grr2;
+use cc_garbling::garbled_circuit::flexor::FleXor;
 
-const INPUTS: [(&str, &str); 15] = [
+const INPUTS: [(&str, &str); 11] = [
     ("adder64", include_str!("../circuits/adder64.txt")),
     ("mult64", include_str!("../circuits/mult64.txt")),
     ("subtract64", include_str!("../circuits/subtract64.txt")),
@@ -15,16 +21,13 @@ const INPUTS: [(&str, &str); 15] = [
     ("aes_192", include_str!("../circuits/aes_192.txt")),
     ("aes_256", include_str!("../circuits/aes_256.txt")),
     ("sha256", include_str!("../circuits/sha256.txt")),
-    ("sha512", include_str!("../circuits/sha512.txt")),
-    ("Keccak_f", include_str!("../circuits/Keccak_f.txt")),
     ("FP-add", include_str!("../circuits/FP-add.txt")),
-    ("FP-div", include_str!("../circuits/FP-div.txt")),
     ("FP-mul", include_str!("../circuits/FP-mul.txt")),
-    ("FP-sqrt", include_str!("../circuits/FP-sqrt.txt")),
 ];
 
 macro_rules! garble {
     ($group:ident, $gc_name:literal, $gc:ty, $circuit_name:ident, $circuit:ident) => {
+        println!("{} {}: {} B", $gc_name, $circuit_name, $circuit.estimated_size::<$gc, _, _>());
         $group.bench_with_input(
             BenchmarkId::new($gc_name, $circuit_name), &$circuit,
             |b, circuit| b.iter(|| {
@@ -37,9 +40,16 @@ fn bench_garble(c: &mut Criterion) {
     let mut g = c.benchmark_group("garble");
     for (name, input) in INPUTS {
         let circuit: Circuit = input.parse().unwrap();
+        println!("{}: {}", name, circuit.stats());
         g.throughput(Throughput::Elements((circuit.input_length + circuit.gates.len()) as u64));
         garble!(g, "HalfGates", HalfGates, name, circuit);
+        garble!(g, "ThreeHalves", ThreeHalves, name, circuit);
         garble!(g, "Classic", Classic, name, circuit);
+        garble!(g, "ClassicFreeXor", ClassicFreeXor, name, circuit);
+        garble!(g, "PointAndPermute", PointAndPermute, name, circuit);
+        garble!(g, "Grr3", Grr3, name, circuit);
+        garble!(g, "Grr2", Grr2, name, circuit);
+        garble!(g, "FleXor", FleXor, name, circuit);
     }
 }
 
@@ -62,7 +72,13 @@ macro_rules! evaluate {
 fn bench_evaluate(c: &mut Criterion) {
     let mut g = c.benchmark_group("evaluate");
     evaluate!(g, "HalfGates", HalfGates);
+    evaluate!(g, "ThreeHalves", ThreeHalves);
     evaluate!(g, "Classic", Classic);
+    evaluate!(g, "ClassicFreeXor", ClassicFreeXor);
+    evaluate!(g, "PointAndPermute", PointAndPermute);
+    evaluate!(g, "Grr3", Grr3);
+    evaluate!(g, "Grr2", Grr2);
+    evaluate!(g, "FleXor", FleXor);
 }
 
 criterion_group!(benches,
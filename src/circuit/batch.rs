@@ -0,0 +1,134 @@
+use std::ops::{BitAnd, BitXor, Not};
+
+use crate::circuit::{Circuit, Gate};
+
+/// A machine word holding the value of one wire in `LANES` independent executions.
+pub trait BitSlice: Copy + BitAnd<Output=Self> + BitXor<Output=Self> + Not<Output=Self> {
+    const LANES: usize;
+    const ZERO: Self;
+    const ONE: Self;
+
+    fn lane(self, lane: usize) -> bool;
+
+    fn set_lane(&mut self, lane: usize, value: bool);
+}
+
+macro_rules! impl_bit_slice {
+    ($t:ty) => {
+        impl BitSlice for $t {
+            const LANES: usize = <$t>::BITS as usize;
+            const ZERO: Self = 0;
+            const ONE: Self = !0;
+
+            fn lane(self, lane: usize) -> bool {
+                (self >> lane) & 1 != 0
+            }
+
+            fn set_lane(&mut self, lane: usize, value: bool) {
+                *self = (*self & !(1 << lane)) | ((value as $t) << lane);
+            }
+        }
+    };
+}
+
+impl_bit_slice!(u64);
+impl_bit_slice!(u128);
+
+/// Transposes up to `W::LANES` bit vectors of length `width` into one word per bit.
+pub fn pack<W: BitSlice>(values: &[Vec<bool>], width: usize) -> Vec<W> {
+    assert!(values.len() <= W::LANES);
+    let mut words = vec![W::ZERO; width];
+    for (lane, value) in values.iter().enumerate() {
+        assert_eq!(value.len(), width);
+        for (word, &bit) in words.iter_mut().zip(value) {
+            word.set_lane(lane, bit);
+        }
+    }
+    words
+}
+
+/// Inverse of [`pack`], returning the first `lanes` bit vectors.
+pub fn unpack<W: BitSlice>(words: &[W], lanes: usize) -> Vec<Vec<bool>> {
+    (0..lanes)
+        .map(|lane| words.iter().map(|w| w.lane(lane)).collect())
+        .collect()
+}
+
+impl Circuit {
+    /// Evaluates the circuit on `W::LANES` inputs at once, where `input` holds one word per
+    /// input wire and lane `i` of every word belongs to execution `i`.
+    ///
+    /// # Panics
+    ///
+    /// If `input` does not hold one word per input wire, or if [`Circuit::validate`] reports
+    /// problems with the circuit.
+    pub fn evaluate_sliced<W: BitSlice>(&self, input: &[W]) -> Vec<W> {
+        assert_eq!(self.input_length, input.len());
+
+        let mut values = vec![W::ZERO; self.input_length + self.gates.len()];
+        values[..self.input_length].copy_from_slice(input);
+
+        for &(output_wire, gate) in self.gates.iter() {
+            values[output_wire] = match gate {
+                Gate::Eq(false) => W::ZERO,
+                Gate::Eq(true) => W::ONE,
+                Gate::EqW(x) => values[x],
+                Gate::Inv(x) => !values[x],
+                Gate::And(x, y) => values[x] & values[y],
+                Gate::Xor(x, y) => values[x] ^ values[y],
+            };
+        }
+
+        values.split_off(values.len() - self.output_length)
+    }
+
+    /// Evaluates the circuit on every input, 64 inputs per pass over the gates.
+    pub fn evaluate_batch(&self, inputs: &[Vec<bool>]) -> Vec<Vec<bool>> {
+        inputs.chunks(u64::LANES)
+            .flat_map(|chunk| {
+                let output = self.evaluate_sliced::<u64>(&pack(chunk, self.input_length));
+                unpack(&output, chunk.len())
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, thread_rng};
+
+    use crate::circuit::{Circuit, pack, unpack};
+
+    fn random_inputs(circuit: &Circuit, count: usize) -> Vec<Vec<bool>> {
+        let mut rng = thread_rng();
+        (0..count)
+            .map(|_| (0..circuit.input_length).map(|_| rng.gen()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_pack() {
+        let values = vec![vec![true, false, true], vec![false, false, true]];
+        let words = pack::<u64>(&values, 3);
+        assert_eq!(words, [0b01, 0b00, 0b11]);
+        assert_eq!(unpack(&words, 2), values);
+    }
+
+    #[test]
+    fn test_aes_128() {
+        let circuit: Circuit = include_str!("../../circuits/aes_128.txt").parse().unwrap();
+        let inputs = random_inputs(&circuit, 100);
+        let expected = inputs.iter()
+            .map(|input| circuit.evaluate(input.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(circuit.evaluate_batch(&inputs), expected);
+    }
+
+    #[test]
+    fn test_u128() {
+        let circuit: Circuit = include_str!("../../circuits/mult64.txt").parse().unwrap();
+        let inputs = random_inputs(&circuit, 128);
+        let output = circuit.evaluate_sliced::<u128>(&pack(&inputs, circuit.input_length));
+        assert_eq!(unpack(&output, 128), circuit.evaluate_batch(&inputs));
+    }
+}
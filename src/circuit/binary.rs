@@ -0,0 +1,384 @@
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::io::{BufRead, Write};
+
+use sha2::{Digest, Sha256};
+
+use crate::circuit::{BristolFormat, Circuit, Gate, GateReader};
+
+const MAGIC: &[u8; 4] = b"GCIR";
+const VERSION: u8 = 1;
+const CHECKSUM_BYTES: usize = 8;
+
+const OP_EQ_FALSE: u8 = 0;
+const OP_EQ_TRUE: u8 = 1;
+const OP_EQW: u8 = 2;
+const OP_INV: u8 = 3;
+const OP_AND: u8 = 4;
+const OP_XOR: u8 = 5;
+const OP_MASK: u8 = 0b0111;
+/// Set when the output wire directly follows the output wire of the previous gate.
+const SEQUENTIAL_OUTPUT: u8 = 0b1000;
+
+/// Error produced when decoding a binary circuit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CircuitDecodeError {
+    /// The data does not start with the expected magic bytes.
+    BadMagic,
+    /// The data was written by an unsupported version of the format.
+    UnsupportedVersion(u8),
+    /// The checksum does not match the contents.
+    ChecksumMismatch,
+    /// The data ended in the middle of a value.
+    UnexpectedEnd,
+    /// A variable length integer does not fit in a `usize`.
+    VarintOverflow,
+    /// A gate has an unknown opcode.
+    InvalidOpcode { gate: usize, opcode: u8 },
+    /// A gate references a wire outside of the circuit.
+    DanglingWire { gate: usize },
+    /// The output groups cover more wires than the circuit has.
+    TooManyOutputs { outputs: usize, wires: usize },
+    /// There is data left after the last gate.
+    TrailingBytes,
+}
+
+impl fmt::Display for CircuitDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CircuitDecodeError::BadMagic => write!(f, "not a binary circuit"),
+            CircuitDecodeError::UnsupportedVersion(v) => write!(f, "unsupported version {}", v),
+            CircuitDecodeError::ChecksumMismatch => write!(f, "checksum mismatch"),
+            CircuitDecodeError::UnexpectedEnd => write!(f, "unexpected end of data"),
+            CircuitDecodeError::VarintOverflow => write!(f, "integer overflow"),
+            CircuitDecodeError::InvalidOpcode { gate, opcode } =>
+                write!(f, "gate {}: invalid opcode {}", gate, opcode),
+            CircuitDecodeError::DanglingWire { gate } =>
+                write!(f, "gate {}: wire out of range", gate),
+            CircuitDecodeError::TooManyOutputs { outputs, wires } =>
+                write!(f, "{} outputs but only {} wires", outputs, wires),
+            CircuitDecodeError::TrailingBytes => write!(f, "trailing bytes after the last gate"),
+        }
+    }
+}
+
+impl Error for CircuitDecodeError {}
+
+/// Writes the binary format while keeping a running checksum of the written bytes.
+struct Encoder<W> {
+    writer: W,
+    hasher: Sha256,
+    previous_output: usize,
+}
+
+impl<W: Write> Encoder<W> {
+    fn new(writer: W, input_groups: &[usize], output_groups: &[usize], gate_count: usize) -> io::Result<Self> {
+        let mut encoder = Encoder {
+            writer,
+            hasher: Sha256::new(),
+            previous_output: input_groups.iter().sum::<usize>().wrapping_sub(1),
+        };
+        encoder.bytes(MAGIC)?;
+        encoder.bytes(&[VERSION])?;
+        for groups in [input_groups, output_groups] {
+            encoder.varint(groups.len() as u64)?;
+            for &size in groups {
+                encoder.varint(size as u64)?;
+            }
+        }
+        encoder.varint(gate_count as u64)?;
+        Ok(encoder)
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        Digest::update(&mut self.hasher, bytes);
+        self.writer.write_all(bytes)
+    }
+
+    fn varint(&mut self, mut n: u64) -> io::Result<()> {
+        let mut buffer = [0u8; 10];
+        let mut length = 0;
+        loop {
+            buffer[length] = (n & 0x7f) as u8;
+            n >>= 7;
+            length += 1;
+            if n == 0 {
+                break;
+            }
+            buffer[length - 1] |= 0x80;
+        }
+        self.bytes(&buffer[..length])
+    }
+
+    /// Writes a wire relative to the output wire of its gate.
+    fn wire(&mut self, output: usize, wire: usize) -> io::Result<()> {
+        self.varint(zigzag(output as i64 - wire as i64))
+    }
+
+    fn gate(&mut self, output: usize, gate: Gate) -> io::Result<()> {
+        let opcode = match gate {
+            Gate::Eq(false) => OP_EQ_FALSE,
+            Gate::Eq(true) => OP_EQ_TRUE,
+            Gate::EqW(_) => OP_EQW,
+            Gate::Inv(_) => OP_INV,
+            Gate::And(_, _) => OP_AND,
+            Gate::Xor(_, _) => OP_XOR,
+        };
+        let expected = self.previous_output.wrapping_add(1);
+        self.previous_output = output;
+        if output == expected {
+            self.bytes(&[opcode | SEQUENTIAL_OUTPUT])?;
+        } else {
+            self.bytes(&[opcode])?;
+            self.varint(zigzag(output as i64 - expected as i64))?;
+        }
+
+        match gate {
+            Gate::Eq(_) => Ok(()),
+            Gate::EqW(a) | Gate::Inv(a) => self.wire(output, a),
+            Gate::And(a, b) | Gate::Xor(a, b) => {
+                self.wire(output, a)?;
+                self.wire(output, b)
+            }
+        }
+    }
+
+    fn finish(mut self) -> io::Result<W> {
+        let checksum = self.hasher.finalize();
+        self.writer.write_all(&checksum[..CHECKSUM_BYTES])?;
+        Ok(self.writer)
+    }
+}
+
+fn zigzag(n: i64) -> u64 {
+    ((n << 1) ^ (n >> 63)) as u64
+}
+
+fn unzigzag(n: u64) -> i64 {
+    (n >> 1) as i64 ^ -((n & 1) as i64)
+}
+
+struct Decoder<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    fn byte(&mut self) -> Result<u8, CircuitDecodeError> {
+        let (&byte, rest) = self.data.split_first()
+            .ok_or(CircuitDecodeError::UnexpectedEnd)?;
+        self.data = rest;
+        Ok(byte)
+    }
+
+    fn varint(&mut self) -> Result<u64, CircuitDecodeError> {
+        let mut n = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            let bits = (byte & 0x7f) as u64;
+            if (bits << shift) >> shift != bits {
+                return Err(CircuitDecodeError::VarintOverflow);
+            }
+            n |= bits << shift;
+            if byte & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+        Err(CircuitDecodeError::VarintOverflow)
+    }
+
+    fn usize(&mut self) -> Result<usize, CircuitDecodeError> {
+        usize::try_from(self.varint()?)
+            .map_err(|_| CircuitDecodeError::VarintOverflow)
+    }
+
+    fn groups(&mut self) -> Result<(Vec<usize>, usize), CircuitDecodeError> {
+        let count = self.usize()?;
+        // Every group takes at least one byte, which bounds the allocation.
+        let mut groups = Vec::with_capacity(count.min(self.data.len()));
+        let mut total = 0usize;
+        for _ in 0..count {
+            let size = self.usize()?;
+            total = total.checked_add(size).ok_or(CircuitDecodeError::VarintOverflow)?;
+            groups.push(size);
+        }
+        Ok((groups, total))
+    }
+
+    /// Reads a wire stored relative to `base`, checking that it lies below `wire_count`.
+    ///
+    /// With `negate` the stored value is `base - wire`, otherwise it is `wire - base`.
+    fn wire(&mut self, gate: usize, base: usize, negate: bool, wire_count: usize) -> Result<usize, CircuitDecodeError> {
+        let delta = unzigzag(self.varint()?);
+        let delta = if negate { delta.wrapping_neg() } else { delta };
+        let magnitude = usize::try_from(delta.unsigned_abs()).ok();
+        let wire = if delta < 0 {
+            magnitude.and_then(|m| base.checked_sub(m))
+        } else {
+            magnitude.and_then(|m| base.checked_add(m))
+        };
+        wire.filter(|&w| w < wire_count)
+            .ok_or(CircuitDecodeError::DanglingWire { gate })
+    }
+}
+
+impl Circuit {
+    /// Encodes the circuit in a compact, versioned binary format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.write_binary(&mut bytes).expect("writing to a Vec cannot fail");
+        bytes
+    }
+
+    /// Writes the circuit in the binary format of [`Circuit::to_bytes`].
+    pub fn write_binary<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut encoder = Encoder::new(writer, &self.input_groups, &self.output_groups, self.gates.len())?;
+        for &(output, gate) in self.gates.iter() {
+            encoder.gate(output, gate)?;
+        }
+        encoder.finish().map(|_| ())
+    }
+
+    /// Decodes a circuit written by [`Circuit::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Circuit, CircuitDecodeError> {
+        if bytes.len() < MAGIC.len() + 1 + CHECKSUM_BYTES {
+            return Err(CircuitDecodeError::UnexpectedEnd);
+        }
+        if &bytes[..MAGIC.len()] != MAGIC {
+            return Err(CircuitDecodeError::BadMagic);
+        }
+        if bytes[MAGIC.len()] != VERSION {
+            return Err(CircuitDecodeError::UnsupportedVersion(bytes[MAGIC.len()]));
+        }
+        let (data, checksum) = bytes.split_at(bytes.len() - CHECKSUM_BYTES);
+        if Sha256::digest(data)[..CHECKSUM_BYTES] != *checksum {
+            return Err(CircuitDecodeError::ChecksumMismatch);
+        }
+
+        let mut decoder = Decoder { data: &data[MAGIC.len() + 1..] };
+        let (input_groups, input_length) = decoder.groups()?;
+        let (output_groups, output_length) = decoder.groups()?;
+        let gate_count = decoder.usize()?;
+        let wire_count = input_length.checked_add(gate_count)
+            .ok_or(CircuitDecodeError::VarintOverflow)?;
+        if output_length > wire_count {
+            return Err(CircuitDecodeError::TooManyOutputs { outputs: output_length, wires: wire_count });
+        }
+
+        // Every gate takes at least one byte, which bounds the allocation.
+        let mut gates = Vec::with_capacity(gate_count.min(decoder.data.len()));
+        let mut previous_output = input_length.wrapping_sub(1);
+        for i in 0..gate_count {
+            let byte = decoder.byte()?;
+            let expected = previous_output.wrapping_add(1);
+            let output = if byte & SEQUENTIAL_OUTPUT != 0 {
+                if expected >= wire_count {
+                    return Err(CircuitDecodeError::DanglingWire { gate: i });
+                }
+                expected
+            } else {
+                decoder.wire(i, expected, false, wire_count)?
+            };
+            previous_output = output;
+
+            let gate = match byte & OP_MASK {
+                OP_EQ_FALSE => Gate::Eq(false),
+                OP_EQ_TRUE => Gate::Eq(true),
+                OP_EQW => Gate::EqW(decoder.wire(i, output, true, wire_count)?),
+                OP_INV => Gate::Inv(decoder.wire(i, output, true, wire_count)?),
+                OP_AND => Gate::And(
+                    decoder.wire(i, output, true, wire_count)?,
+                    decoder.wire(i, output, true, wire_count)?,
+                ),
+                OP_XOR => Gate::Xor(
+                    decoder.wire(i, output, true, wire_count)?,
+                    decoder.wire(i, output, true, wire_count)?,
+                ),
+                opcode => return Err(CircuitDecodeError::InvalidOpcode { gate: i, opcode }),
+            };
+            gates.push((output, gate));
+        }
+        if !decoder.data.is_empty() {
+            return Err(CircuitDecodeError::TrailingBytes);
+        }
+
+        Ok(Circuit {
+            input_length,
+            output_length,
+            input_groups,
+            output_groups,
+            gates,
+        })
+    }
+}
+
+/// Converts a Bristol Fashion circuit to the binary format without materializing it.
+pub fn bristol_to_binary<R: BufRead, W: Write>(reader: R, writer: W) -> io::Result<()> {
+    let invalid = |e| io::Error::new(io::ErrorKind::InvalidData, e);
+    let gates = GateReader::new(reader, BristolFormat::Fashion).map_err(invalid)?;
+    let header = gates.header().clone();
+    let gate_count = header.wire_count.saturating_sub(header.input_groups.iter().sum::<usize>());
+
+    let mut encoder = Encoder::new(writer, &header.input_groups, &header.output_groups, gate_count)?;
+    for gate in gates {
+        let (output, gate) = gate.map_err(invalid)?;
+        encoder.gate(output, gate)?;
+    }
+    encoder.finish().map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::{Digest, Sha256};
+
+    use crate::circuit::{bristol_to_binary, Circuit, CircuitDecodeError};
+
+    #[test]
+    fn test_round_trip() {
+        let circuits = [
+            include_str!("../../circuits/adder64.txt"),
+            include_str!("../../circuits/aes_128.txt"),
+            include_str!("../../circuits/sha256.txt"),
+            include_str!("../../circuits/udivide64.txt"),
+            "4 7\n1 2\n1 2\n\n1 1 1 2 EQ\n1 1 0 3 EQW\n1 1 3 4 NOT\n4 2 0 1 2 4 5 6 MAND\n",
+        ];
+        for text in circuits {
+            let circuit: Circuit = text.parse().unwrap();
+            let bytes = circuit.to_bytes();
+            assert!(bytes.len() < text.len());
+            assert_eq!(Circuit::from_bytes(&bytes).unwrap(), circuit);
+
+            let mut converted = Vec::new();
+            bristol_to_binary(text.as_bytes(), &mut converted).unwrap();
+            assert_eq!(converted, bytes);
+        }
+    }
+
+    #[test]
+    fn test_corrupted() {
+        let circuit: Circuit = include_str!("../../circuits/zero_equal.txt").parse().unwrap();
+        let bytes = circuit.to_bytes();
+
+        assert_eq!(Circuit::from_bytes(&bytes[..3]), Err(CircuitDecodeError::UnexpectedEnd));
+        assert_eq!(Circuit::from_bytes(b"BGCF\x01\0\0\0\0\0\0\0\0"), Err(CircuitDecodeError::BadMagic));
+        assert_eq!(Circuit::from_bytes(b"GCIR\x07\0\0\0\0\0\0\0\0"), Err(CircuitDecodeError::UnsupportedVersion(7)));
+
+        let mut flipped = bytes.clone();
+        flipped[20] ^= 1;
+        assert_eq!(Circuit::from_bytes(&flipped), Err(CircuitDecodeError::ChecksumMismatch));
+
+        let mut truncated = bytes[..bytes.len() - 10].to_vec();
+        let checksum = Sha256::digest(&truncated);
+        truncated.extend(&checksum[..8]);
+        assert_eq!(Circuit::from_bytes(&truncated), Err(CircuitDecodeError::UnexpectedEnd));
+
+        let mut outputs: Circuit = "1 3\n1 2\n1 1\n\n2 1 0 1 2 AND\n".parse().unwrap();
+        outputs.output_groups = vec![10];
+        outputs.output_length = 10;
+        assert_eq!(
+            Circuit::from_bytes(&outputs.to_bytes()),
+            Err(CircuitDecodeError::TooManyOutputs { outputs: 10, wires: 3 })
+        );
+    }
+}
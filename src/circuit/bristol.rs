@@ -0,0 +1,685 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::io::BufRead;
+use std::str::FromStr;
+
+use crate::circuit::{Circuit, Gate};
+
+/// Error produced when a Bristol Fashion circuit description is malformed.
+///
+/// Lines and columns are 1-indexed and refer to the start of the offending token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CircuitParseError {
+    /// The header is missing, truncated or does not have the expected shape.
+    BadHeader { line: usize, reason: &'static str },
+    /// A token that should have been a number could not be parsed as one.
+    InvalidNumber { line: usize, column: usize, token: String },
+    /// The wire count in the header does not equal the input count plus one wire per gate output.
+    WireCountMismatch { line: usize, expected: usize, found: usize },
+    /// The number of gate lines does not match the gate count in the header.
+    GateCountMismatch { line: usize, expected: usize, found: usize },
+    /// The gate operation is not one of EQ, EQW, INV, NOT, AND, XOR or MAND.
+    UnknownOperation { line: usize, column: usize, token: String },
+    /// The operation does not take the given number of inputs.
+    ArityMismatch { line: usize, column: usize, operation: String, inputs: usize },
+    /// A gate other than MAND declares more than one output wire.
+    MultiOutputGate { line: usize, column: usize, outputs: usize },
+    /// The gate references a wire outside of the declared wire range.
+    DanglingWire { line: usize, column: usize, wire: usize },
+    /// The line ended before all expected tokens were read.
+    UnexpectedEndOfLine { line: usize },
+    /// The line contains tokens after the gate operation.
+    TrailingToken { line: usize, column: usize, token: String },
+    /// The underlying reader failed, e.g. because the input is not valid UTF-8.
+    Io { line: usize, kind: io::ErrorKind },
+}
+
+impl fmt::Display for CircuitParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CircuitParseError::BadHeader { line, reason } =>
+                write!(f, "line {}: bad header: {}", line, reason),
+            CircuitParseError::InvalidNumber { line, column, token } =>
+                write!(f, "line {}, column {}: invalid number '{}'", line, column, token),
+            CircuitParseError::WireCountMismatch { line, expected, found } =>
+                write!(f, "line {}: expected {} wires, found {}", line, expected, found),
+            CircuitParseError::GateCountMismatch { line, expected, found } =>
+                write!(f, "line {}: expected {} gates, found {}", line, expected, found),
+            CircuitParseError::UnknownOperation { line, column, token } =>
+                write!(f, "line {}, column {}: unknown operation '{}'", line, column, token),
+            CircuitParseError::ArityMismatch { line, column, operation, inputs } =>
+                write!(f, "line {}, column {}: operation '{}' does not take {} inputs", line, column, operation, inputs),
+            CircuitParseError::MultiOutputGate { line, column, outputs } =>
+                write!(f, "line {}, column {}: gate has {} outputs, expected 1", line, column, outputs),
+            CircuitParseError::DanglingWire { line, column, wire } =>
+                write!(f, "line {}, column {}: wire {} is out of range", line, column, wire),
+            CircuitParseError::UnexpectedEndOfLine { line } =>
+                write!(f, "line {}: unexpected end of line", line),
+            CircuitParseError::TrailingToken { line, column, token } =>
+                write!(f, "line {}, column {}: unexpected token '{}'", line, column, token),
+            CircuitParseError::Io { line, kind } =>
+                write!(f, "line {}: read failed: {}", line, kind),
+        }
+    }
+}
+
+impl Error for CircuitParseError {}
+
+/// Whitespace separated tokens of a single line along with their column.
+struct Tokens<'a> {
+    line: usize,
+    text: &'a str,
+    offset: usize,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(line: usize, text: &'a str) -> Self {
+        Tokens { line, text, offset: 0 }
+    }
+
+    fn next_token(&mut self) -> Option<(usize, &'a str)> {
+        let rest = &self.text[self.offset..];
+        let start = self.offset + rest.len() - rest.trim_start().len();
+        let rest = &self.text[start..];
+        if rest.is_empty() {
+            self.offset = start;
+            return None;
+        }
+        let end = start + rest.find(char::is_whitespace).unwrap_or(rest.len());
+        self.offset = end;
+        Some((start + 1, &self.text[start..end]))
+    }
+
+    fn token(&mut self) -> Result<(usize, &'a str), CircuitParseError> {
+        self.next_token()
+            .ok_or(CircuitParseError::UnexpectedEndOfLine { line: self.line })
+    }
+
+    fn number(&mut self) -> Result<(usize, usize), CircuitParseError> {
+        let (column, token) = self.token()?;
+        token.parse()
+            .map(|n| (column, n))
+            .map_err(|_| CircuitParseError::InvalidNumber {
+                line: self.line,
+                column,
+                token: token.to_string(),
+            })
+    }
+
+    fn wire(&mut self, wire_count: usize) -> Result<usize, CircuitParseError> {
+        let (column, wire) = self.number()?;
+        if wire >= wire_count {
+            return Err(CircuitParseError::DanglingWire { line: self.line, column, wire });
+        }
+        Ok(wire)
+    }
+
+    fn finish(&mut self) -> Result<(), CircuitParseError> {
+        match self.next_token() {
+            Some((column, token)) => Err(CircuitParseError::TrailingToken {
+                line: self.line,
+                column,
+                token: token.to_string(),
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Parses a header line of the form `n x_1 ... x_n` and returns the `x_i`.
+fn parse_groups(line: usize, text: &str) -> Result<Vec<usize>, CircuitParseError> {
+    let mut tokens = Tokens::new(line, text);
+    let (_, count) = tokens.number()?;
+    let groups = (0..count)
+        .map(|_| tokens.number().map(|(_, n)| n))
+        .collect::<Result<Vec<_>, _>>()?;
+    tokens.finish()?;
+    Ok(groups)
+}
+
+/// Adds up the sizes of a group header line, which may not exceed `usize::MAX` in total.
+fn group_sum(line: usize, groups: &[usize]) -> Result<usize, CircuitParseError> {
+    groups.iter()
+        .try_fold(0usize, |sum, &size| sum.checked_add(size))
+        .ok_or(CircuitParseError::BadHeader { line, reason: "group sizes overflow" })
+}
+
+fn parse_gate(tokens: &mut Tokens, wire_count: usize, gates: &mut Vec<(usize, Gate)>) -> Result<(), CircuitParseError> {
+    let line = tokens.line;
+    let (_, input_count) = tokens.number()?;
+    let (output_column, output_count) = tokens.number()?;
+
+    let inputs = (0..input_count)
+        .map(|_| tokens.number())
+        .collect::<Result<Vec<_>, _>>()?;
+    let outputs = (0..output_count)
+        .map(|_| tokens.wire(wire_count))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let (column, operation) = tokens.token()?;
+    let arity_mismatch = || CircuitParseError::ArityMismatch {
+        line,
+        column,
+        operation: operation.to_string(),
+        inputs: input_count,
+    };
+    if !matches!(operation, "EQ" | "EQW" | "INV" | "NOT" | "AND" | "XOR" | "MAND") {
+        return Err(CircuitParseError::UnknownOperation {
+            line,
+            column,
+            token: operation.to_string(),
+        });
+    }
+    if operation != "MAND" && output_count != 1 {
+        return Err(CircuitParseError::MultiOutputGate { line, column: output_column, outputs: output_count });
+    }
+
+    tokens.finish()?;
+
+    // EQ takes a constant rather than a wire as its input.
+    if operation == "EQ" {
+        let constant = match inputs.as_slice() {
+            [(_, 0)] => false,
+            [(_, 1)] => true,
+            &[(column, n)] => return Err(CircuitParseError::InvalidNumber { line, column, token: n.to_string() }),
+            _ => return Err(arity_mismatch()),
+        };
+        gates.push((outputs[0], Gate::Eq(constant)));
+        return Ok(());
+    }
+    let inputs = inputs.into_iter()
+        .map(|(column, wire)| if wire < wire_count {
+            Ok(wire)
+        } else {
+            Err(CircuitParseError::DanglingWire { line, column, wire })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match (operation, inputs.as_slice()) {
+        ("EQW", &[a]) => gates.push((outputs[0], Gate::EqW(a))),
+        ("INV", &[a]) | ("NOT", &[a]) => gates.push((outputs[0], Gate::Inv(a))),
+        ("AND", &[a, b]) => gates.push((outputs[0], Gate::And(a, b))),
+        ("XOR", &[a, b]) => gates.push((outputs[0], Gate::Xor(a, b))),
+        ("MAND", _) if input_count == 2 * output_count => {
+            let (left, right) = inputs.split_at(output_count);
+            gates.extend(outputs.iter()
+                .zip(left.iter().zip(right))
+                .map(|(&output, (&a, &b))| (output, Gate::And(a, b))));
+        }
+        _ => return Err(arity_mismatch()),
+    }
+    Ok(())
+}
+
+/// Variant of the Bristol circuit format.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BristolFormat {
+    /// Bristol Fashion, with a header line for the input values and one for the output values.
+    Fashion,
+    /// The original Bristol format, with a single header line holding the sizes of the two
+    /// inputs and of the output.
+    Legacy,
+}
+
+impl BristolFormat {
+    /// Guesses the format from the shape of the header, or returns `None` if neither fits.
+    pub fn detect(s: &str) -> Option<BristolFormat> {
+        let mut lines = s.lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| l.split_ascii_whitespace()
+                .map(|t| t.parse::<usize>().ok())
+                .collect::<Option<Vec<_>>>());
+        let is_groups = |line: &Option<Vec<usize>>| matches!(line, Some(n) if !n.is_empty() && n[0] + 1 == n.len());
+
+        if !matches!(lines.next()?, Some(n) if n.len() == 2) {
+            return None;
+        }
+        let inputs = lines.next()?;
+        let outputs = lines.next();
+        if is_groups(&inputs) && outputs.as_ref().is_some_and(is_groups) {
+            Some(BristolFormat::Fashion)
+        } else if matches!(inputs, Some(n) if n.len() == 3) {
+            Some(BristolFormat::Legacy)
+        } else {
+            None
+        }
+    }
+}
+
+/// Header of a Bristol circuit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BristolHeader {
+    /// Number of gate lines; a MAND line counts as a single gate.
+    pub gate_count: usize,
+    pub wire_count: usize,
+    pub input_groups: Vec<usize>,
+    pub output_groups: Vec<usize>,
+}
+
+/// Reads a Bristol circuit line by line, yielding one gate at a time.
+///
+/// Only the current line is kept in memory, so arbitrarily large netlists can be consumed
+/// without materializing a [`Circuit`]. Once an error has been yielded the iterator is exhausted.
+pub struct GateReader<R> {
+    reader: R,
+    header: BristolHeader,
+    header_line: usize,
+    input_length: usize,
+    output_length: usize,
+    buffer: String,
+    line: usize,
+    last_gate_line: usize,
+    gate_lines: usize,
+    gate_outputs: usize,
+    pending: Vec<(usize, Gate)>,
+    done: bool,
+}
+
+/// Reads the next non-empty line into `buffer` and returns its line number.
+fn read_line<R: BufRead>(reader: &mut R, buffer: &mut String, line: &mut usize) -> Result<Option<usize>, CircuitParseError> {
+    loop {
+        buffer.clear();
+        *line += 1;
+        let read = reader.read_line(buffer)
+            .map_err(|e| CircuitParseError::Io { line: *line, kind: e.kind() })?;
+        if read == 0 {
+            return Ok(None);
+        }
+        if !buffer.trim().is_empty() {
+            return Ok(Some(*line));
+        }
+    }
+}
+
+impl<R: BufRead> GateReader<R> {
+    /// Reads the header of a circuit in the given format.
+    pub fn new(mut reader: R, format: BristolFormat) -> Result<Self, CircuitParseError> {
+        let mut buffer = String::new();
+        let mut line = 0;
+
+        let header_line = read_line(&mut reader, &mut buffer, &mut line)?
+            .ok_or(CircuitParseError::BadHeader { line: 1, reason: "missing gate and wire count" })?;
+        let mut tokens = Tokens::new(header_line, &buffer);
+        let (_, gate_count) = tokens.number()?;
+        let (_, wire_count) = tokens.number()?;
+        tokens.finish()?;
+
+        let (input_length, output_line, output_length, input_groups, output_groups) = match format {
+            BristolFormat::Fashion => {
+                let input_line = read_line(&mut reader, &mut buffer, &mut line)?
+                    .ok_or(CircuitParseError::BadHeader { line: header_line + 1, reason: "missing input counts" })?;
+                let input_groups = parse_groups(input_line, &buffer)?;
+                let input_length = group_sum(input_line, &input_groups)?;
+                let output_line = read_line(&mut reader, &mut buffer, &mut line)?
+                    .ok_or(CircuitParseError::BadHeader { line: input_line + 1, reason: "missing output counts" })?;
+                let output_groups = parse_groups(output_line, &buffer)?;
+                let output_length = group_sum(output_line, &output_groups)?;
+                (input_length, output_line, output_length, input_groups, output_groups)
+            }
+            BristolFormat::Legacy => {
+                let input_line = read_line(&mut reader, &mut buffer, &mut line)?
+                    .ok_or(CircuitParseError::BadHeader { line: header_line + 1, reason: "missing input and output counts" })?;
+                let mut tokens = Tokens::new(input_line, &buffer);
+                let (_, left) = tokens.number()?;
+                let (_, right) = tokens.number()?;
+                let (_, output) = tokens.number()?;
+                tokens.finish()?;
+                let input_groups = vec![left, right];
+                (group_sum(input_line, &input_groups)?, input_line, output, input_groups, vec![output])
+            }
+        };
+        // The outputs are the last wires, so there cannot be more of them than wires.
+        if output_length > wire_count {
+            return Err(CircuitParseError::BadHeader { line: output_line, reason: "more outputs than wires" });
+        }
+
+        Ok(GateReader {
+            reader,
+            header: BristolHeader { gate_count, wire_count, input_groups, output_groups },
+            header_line,
+            input_length,
+            output_length,
+            buffer,
+            last_gate_line: line,
+            line,
+            gate_lines: 0,
+            gate_outputs: 0,
+            pending: Vec::new(),
+            done: false,
+        })
+    }
+
+    pub fn header(&self) -> &BristolHeader {
+        &self.header
+    }
+
+    /// Checks the gate and wire counts once all gates have been read.
+    fn finish(&self) -> Result<(), CircuitParseError> {
+        if self.gate_lines != self.header.gate_count {
+            return Err(CircuitParseError::GateCountMismatch {
+                line: self.last_gate_line,
+                expected: self.header.gate_count,
+                found: self.gate_lines,
+            });
+        }
+        match self.input_length.checked_add(self.gate_outputs) {
+            Some(expected) if expected == self.header.wire_count => Ok(()),
+            expected => Err(CircuitParseError::WireCountMismatch {
+                line: self.header_line,
+                expected: expected.unwrap_or(usize::MAX),
+                found: self.header.wire_count,
+            }),
+        }
+    }
+
+    fn next_gates(&mut self) -> Result<bool, CircuitParseError> {
+        match read_line(&mut self.reader, &mut self.buffer, &mut self.line)? {
+            Some(line) => {
+                parse_gate(&mut Tokens::new(line, &self.buffer), self.header.wire_count, &mut self.pending)?;
+                self.pending.reverse();
+                self.gate_lines += 1;
+                self.gate_outputs += self.pending.len();
+                self.last_gate_line = line;
+                Ok(true)
+            }
+            None => self.finish().map(|_| false),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for GateReader<R> {
+    type Item = Result<(usize, Gate), CircuitParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(gate) = self.pending.pop() {
+            return Some(Ok(gate));
+        }
+        if self.done {
+            return None;
+        }
+        match self.next_gates() {
+            Ok(true) => self.pending.pop().map(Ok),
+            Ok(false) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+fn parse_bristol<R: BufRead>(reader: R, format: BristolFormat) -> Result<Circuit, CircuitParseError> {
+    let mut gates = GateReader::new(reader, format)?;
+    let BristolHeader { input_groups, output_groups, .. } = gates.header().clone();
+    let (input_length, output_length) = (gates.input_length, gates.output_length);
+    // The header counts are not trusted to reserve memory, since a short file can claim any
+    // number of gates.
+
+    let mut real_gates = Vec::new();
+    for gate in &mut gates {
+        real_gates.push(gate?);
+    }
+
+    Ok(Circuit {
+        input_length,
+        output_length,
+        input_groups,
+        output_groups,
+        gates: real_gates,
+    })
+}
+
+impl FromStr for Circuit {
+    type Err = CircuitParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_bristol(s.as_bytes(), BristolFormat::Fashion)
+    }
+}
+
+impl Circuit {
+    /// Parses a circuit in the original Bristol format.
+    pub fn from_bristol_legacy(s: &str) -> Result<Circuit, CircuitParseError> {
+        parse_bristol(s.as_bytes(), BristolFormat::Legacy)
+    }
+
+    /// Parses a Bristol Fashion circuit from a reader without loading the whole text.
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Circuit, CircuitParseError> {
+        parse_bristol(reader, BristolFormat::Fashion)
+    }
+
+    /// Parses a circuit in either Bristol format, as determined by [`BristolFormat::detect`].
+    ///
+    /// Falls back to Bristol Fashion if the format cannot be detected.
+    pub fn from_bristol(s: &str) -> Result<Circuit, CircuitParseError> {
+        parse_bristol(s.as_bytes(), BristolFormat::detect(s).unwrap_or(BristolFormat::Fashion))
+    }
+
+    /// Writes the circuit in Bristol Fashion.
+    pub fn write_bristol<W: io::Write>(&self, mut w: W) -> io::Result<()> {
+        write!(w, "{}", self)
+    }
+}
+
+fn write_groups(f: &mut fmt::Formatter<'_>, groups: &[usize]) -> fmt::Result {
+    write!(f, "{}", groups.len())?;
+    for size in groups {
+        write!(f, " {}", size)?;
+    }
+    writeln!(f)
+}
+
+impl fmt::Display for Circuit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} {}", self.gates.len(), self.input_length + self.gates.len())?;
+        write_groups(f, &self.input_groups)?;
+        write_groups(f, &self.output_groups)?;
+        writeln!(f)?;
+
+        for &(output, gate) in self.gates.iter() {
+            match gate {
+                Gate::Eq(c) => writeln!(f, "1 1 {} {} EQ", c as u8, output)?,
+                Gate::EqW(a) => writeln!(f, "1 1 {} {} EQW", a, output)?,
+                Gate::Inv(a) => writeln!(f, "1 1 {} {} INV", a, output)?,
+                Gate::And(a, b) => writeln!(f, "2 1 {} {} {} AND", a, b, output)?,
+                Gate::Xor(a, b) => writeln!(f, "2 1 {} {} {} XOR", a, b, output)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    use crate::circuit::{BristolFormat, Circuit, CircuitParseError, Gate, GateReader};
+
+    fn parse(s: &str) -> Result<Circuit, CircuitParseError> {
+        s.parse()
+    }
+
+    #[test]
+    fn test_valid() {
+        let circuit = parse("2 4\n1 2\n1 1\n\n2 1 0 1 2 AND\n1 1 2 3 INV\n").unwrap();
+        assert_eq!(circuit.input_length, 2);
+        assert_eq!(circuit.output_length, 1);
+        assert_eq!(circuit.input_groups, [2]);
+        assert_eq!(circuit.output_groups, [1]);
+        assert_eq!(circuit.gates.len(), 2);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let circuits = [
+            include_str!("../../circuits/adder64.txt"),
+            include_str!("../../circuits/aes_128.txt"),
+            include_str!("../../circuits/aes_192.txt"),
+            include_str!("../../circuits/aes_256.txt"),
+            include_str!("../../circuits/FP-add.txt"),
+            include_str!("../../circuits/FP-mul.txt"),
+            include_str!("../../circuits/mult64.txt"),
+            include_str!("../../circuits/sha256.txt"),
+            include_str!("../../circuits/subtract64.txt"),
+            include_str!("../../circuits/udivide64.txt"),
+            include_str!("../../circuits/zero_equal.txt"),
+            "4 7\n1 2\n1 2\n\n1 1 1 2 EQ\n1 1 0 3 EQW\n1 1 3 4 NOT\n4 2 0 1 2 4 5 6 MAND\n",
+        ];
+        for text in circuits {
+            let circuit = parse(text).unwrap();
+            let mut written = Vec::new();
+            circuit.write_bristol(&mut written).unwrap();
+            assert_eq!(parse(std::str::from_utf8(&written).unwrap()).unwrap(), circuit);
+        }
+    }
+
+    #[test]
+    fn test_legacy() {
+        let fashion = include_str!("../../circuits/adder64.txt");
+        let (_, gates) = fashion.split_once("\n\n").unwrap();
+        let legacy = format!("376 504\n64 64 64\n\n{}", gates);
+
+        assert_eq!(BristolFormat::detect(fashion), Some(BristolFormat::Fashion));
+        assert_eq!(BristolFormat::detect(&legacy), Some(BristolFormat::Legacy));
+        assert_eq!(BristolFormat::detect("1 2 3\n"), None);
+
+        let circuit = Circuit::from_bristol_legacy(&legacy).unwrap();
+        assert_eq!(circuit, parse(fashion).unwrap());
+        assert_eq!(Circuit::from_bristol(&legacy).unwrap(), circuit);
+        assert_eq!(Circuit::from_bristol(fashion).unwrap(), circuit);
+
+        assert_eq!(
+            Circuit::from_bristol_legacy("1 3\n1 1\n\n2 1 0 1 2 AND\n"),
+            Err(CircuitParseError::UnexpectedEndOfLine { line: 2 })
+        );
+    }
+
+    #[test]
+    fn test_reader() {
+        let text = include_str!("../../circuits/sha256.txt");
+        let circuit = Circuit::from_reader(BufReader::new(text.as_bytes())).unwrap();
+        assert_eq!(circuit, parse(text).unwrap());
+
+        let mut gates = GateReader::new(text.as_bytes(), BristolFormat::Fashion).unwrap();
+        assert_eq!(gates.header().input_groups, [512, 256]);
+        assert_eq!(gates.next(), Some(Ok(circuit.gates[0])));
+        assert_eq!(gates.count(), circuit.gates.len() - 1);
+
+        let mut gates = GateReader::new("2 4\n1 2\n1 1\n\n4 2 0 1 0 1 2 3 MAND\n".as_bytes(), BristolFormat::Fashion).unwrap();
+        assert_eq!(gates.next(), Some(Ok((2, Gate::And(0, 0)))));
+        assert_eq!(gates.next(), Some(Ok((3, Gate::And(1, 1)))));
+        assert_eq!(gates.next(), Some(Err(CircuitParseError::GateCountMismatch { line: 5, expected: 2, found: 1 })));
+        assert_eq!(gates.next(), None);
+
+        assert_eq!(
+            Circuit::from_reader(&b"1 3\n1 2\n1 1\n\n2 1 0 1 2 \xff\n"[..]),
+            Err(CircuitParseError::Io { line: 5, kind: std::io::ErrorKind::InvalidData })
+        );
+    }
+
+    #[test]
+    fn test_bad_header() {
+        assert_eq!(parse(""), Err(CircuitParseError::BadHeader { line: 1, reason: "missing gate and wire count" }));
+        assert_eq!(parse("1 3\n1 2\n"), Err(CircuitParseError::BadHeader { line: 3, reason: "missing output counts" }));
+        assert_eq!(parse("1 x\n"), Err(CircuitParseError::InvalidNumber { line: 1, column: 3, token: "x".to_string() }));
+        assert_eq!(parse("1 3\n2 2\n"), Err(CircuitParseError::UnexpectedEndOfLine { line: 2 }));
+
+        // Huge or overflowing counts are rejected without reserving memory for them.
+        assert_eq!(
+            parse("1 18446744073709551615\n1 2\n1 1\n\n2 1 0 1 2 AND\n"),
+            Err(CircuitParseError::WireCountMismatch { line: 1, expected: 3, found: usize::MAX })
+        );
+        assert_eq!(
+            parse("1 1000000000000\n1 2\n1 1\n\n2 1 0 1 2 AND\n"),
+            Err(CircuitParseError::WireCountMismatch { line: 1, expected: 3, found: 1000000000000 })
+        );
+        assert_eq!(
+            parse("1 3\n2 18446744073709551615 5\n1 1\n\n2 1 0 1 2 AND\n"),
+            Err(CircuitParseError::BadHeader { line: 2, reason: "group sizes overflow" })
+        );
+        assert_eq!(
+            parse("1 3\n1 2\n1 10\n\n2 1 0 1 2 AND\n"),
+            Err(CircuitParseError::BadHeader { line: 3, reason: "more outputs than wires" })
+        );
+        assert_eq!(
+            Circuit::from_bristol_legacy("1 3\n1 1 10\n\n2 1 0 1 2 AND\n"),
+            Err(CircuitParseError::BadHeader { line: 2, reason: "more outputs than wires" })
+        );
+        assert_eq!(
+            Circuit::from_bristol_legacy("1 3\n18446744073709551615 1 1\n\n2 1 0 1 2 AND\n"),
+            Err(CircuitParseError::BadHeader { line: 2, reason: "group sizes overflow" })
+        );
+    }
+
+    #[test]
+    fn test_extended_gates() {
+        let circuit = parse("4 7\n1 2\n1 2\n\n1 1 1 2 EQ\n1 1 0 3 EQW\n1 1 3 4 NOT\n4 2 0 1 2 4 5 6 MAND\n").unwrap();
+        assert_eq!(circuit.gates, [
+            (2, Gate::Eq(true)),
+            (3, Gate::EqW(0)),
+            (4, Gate::Inv(3)),
+            (5, Gate::And(0, 2)),
+            (6, Gate::And(1, 4)),
+        ]);
+        assert!(circuit.verify_topology());
+        assert_eq!(circuit.evaluate(vec![true, false]), [true, false]);
+        assert_eq!(circuit.evaluate(vec![false, true]), [false, true]);
+
+        assert_eq!(
+            parse("1 3\n1 2\n1 1\n\n1 1 2 2 EQ\n"),
+            Err(CircuitParseError::InvalidNumber { line: 5, column: 5, token: "2".to_string() })
+        );
+        assert_eq!(
+            parse("1 5\n1 2\n1 2\n\n3 2 0 1 0 3 4 MAND\n"),
+            Err(CircuitParseError::ArityMismatch { line: 5, column: 15, operation: "MAND".to_string(), inputs: 3 })
+        );
+    }
+
+    #[test]
+    fn test_wire_count_mismatch() {
+        assert_eq!(
+            parse("1 4\n1 2\n1 1\n\n2 1 0 1 2 AND\n"),
+            Err(CircuitParseError::WireCountMismatch { line: 1, expected: 3, found: 4 })
+        );
+    }
+
+    #[test]
+    fn test_gate_count_mismatch() {
+        assert_eq!(
+            parse("2 4\n1 2\n1 1\n\n2 1 0 1 2 AND\n"),
+            Err(CircuitParseError::GateCountMismatch { line: 5, expected: 2, found: 1 })
+        );
+    }
+
+    #[test]
+    fn test_bad_gates() {
+        assert_eq!(
+            parse("1 3\n1 2\n1 1\n\n2 1 0 1 2 NAND\n"),
+            Err(CircuitParseError::UnknownOperation { line: 5, column: 11, token: "NAND".to_string() })
+        );
+        assert_eq!(
+            parse("1 3\n1 2\n1 1\n\n1 1 0 2 AND\n"),
+            Err(CircuitParseError::ArityMismatch { line: 5, column: 9, operation: "AND".to_string(), inputs: 1 })
+        );
+        assert_eq!(
+            parse("1 4\n1 2\n1 1\n\n2 2 0 1 2 3 AND\n"),
+            Err(CircuitParseError::MultiOutputGate { line: 5, column: 3, outputs: 2 })
+        );
+        assert_eq!(
+            parse("1 3\n1 2\n1 1\n\n2 1 0 7 2 AND\n"),
+            Err(CircuitParseError::DanglingWire { line: 5, column: 7, wire: 7 })
+        );
+        assert_eq!(
+            parse("1 3\n1 2\n1 1\n\n2 1 0 1 2\n"),
+            Err(CircuitParseError::UnexpectedEndOfLine { line: 5 })
+        );
+        assert_eq!(
+            parse("1 3\n1 2\n1 1\n\n2 1 0 1 2 AND 4\n"),
+            Err(CircuitParseError::TrailingToken { line: 5, column: 15, token: "4".to_string() })
+        );
+    }
+}
@@ -0,0 +1,213 @@
+use crate::circuit::{Circuit, Gate};
+
+/// Handle to a wire of a circuit under construction.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Wire(pub(crate) usize);
+
+/// Builds a [`Circuit`] gate by gate.
+///
+/// Wires are handed out as [`Wire`] handles and only receive their final numbers in
+/// [`CircuitBuilder::build`], which places the inputs first and the outputs last.
+#[derive(Debug, Clone, Default)]
+pub struct CircuitBuilder {
+    wire_count: usize,
+    inputs: Vec<usize>,
+    input_groups: Vec<usize>,
+    gates: Vec<(usize, Gate)>,
+    outputs: Vec<usize>,
+    output_groups: Vec<usize>,
+}
+
+impl CircuitBuilder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    fn wire(&mut self) -> usize {
+        self.wire_count += 1;
+        self.wire_count - 1
+    }
+
+    fn gate(&mut self, gate: Gate) -> Wire {
+        let output = self.wire();
+        self.gates.push((output, gate));
+        Wire(output)
+    }
+
+    /// Adds a new input group of `size` wires.
+    pub fn inputs(&mut self, size: usize) -> Vec<Wire> {
+        self.input_groups.push(size);
+        (0..size)
+            .map(|_| {
+                let wire = self.wire();
+                self.inputs.push(wire);
+                Wire(wire)
+            })
+            .collect()
+    }
+
+    /// Adds a new input group holding a single wire.
+    pub fn input(&mut self) -> Wire {
+        self.inputs(1)[0]
+    }
+
+    pub fn constant(&mut self, value: bool) -> Wire {
+        self.gate(Gate::Eq(value))
+    }
+
+    pub fn and(&mut self, a: Wire, b: Wire) -> Wire {
+        self.gate(Gate::And(a.0, b.0))
+    }
+
+    pub fn xor(&mut self, a: Wire, b: Wire) -> Wire {
+        self.gate(Gate::Xor(a.0, b.0))
+    }
+
+    pub fn not(&mut self, a: Wire) -> Wire {
+        self.gate(Gate::Inv(a.0))
+    }
+
+    /// `a | b`, using a single AND gate.
+    pub fn or(&mut self, a: Wire, b: Wire) -> Wire {
+        let x = self.xor(a, b);
+        let y = self.and(a, b);
+        self.xor(x, y)
+    }
+
+    /// Returns `b` if `select` is set and `a` otherwise, using a single AND gate.
+    pub fn mux(&mut self, select: Wire, a: Wire, b: Wire) -> Wire {
+        let d = self.xor(a, b);
+        let d = self.and(select, d);
+        self.xor(a, d)
+    }
+
+    /// Marks `wires` as a new output group.
+    pub fn outputs(&mut self, wires: &[Wire]) {
+        self.output_groups.push(wires.len());
+        self.outputs.extend(wires.iter().map(|w| w.0));
+    }
+
+    /// Marks `wire` as a new output group holding a single wire.
+    pub fn output(&mut self, wire: Wire) {
+        self.outputs(&[wire]);
+    }
+
+    pub fn build(self) -> Circuit {
+        renumber(
+            self.wire_count,
+            &self.inputs,
+            self.input_groups,
+            &self.gates,
+            &self.outputs,
+            self.output_groups,
+        )
+    }
+}
+
+/// Assembles a circuit from gates over arbitrary wire ids below `wire_count`.
+///
+/// The gates must be in topological order. Inputs are numbered first and non-output wires
+/// densely after them. Outputs become the last wires, using EQW gates for outputs that are
+/// inputs or that appear more than once.
+pub(crate) fn renumber(
+    wire_count: usize,
+    inputs: &[usize],
+    input_groups: Vec<usize>,
+    gates: &[(usize, Gate)],
+    outputs: &[usize],
+    output_groups: Vec<usize>,
+) -> Circuit {
+    let mut is_gate_output = vec![false; wire_count];
+    for &(output, _) in gates {
+        is_gate_output[output] = true;
+    }
+
+    // Claim the gate producing each output wire where possible.
+    let mut output_position = vec![None; wire_count];
+    let mut copies = vec![];
+    for (k, &wire) in outputs.iter().enumerate() {
+        if is_gate_output[wire] && output_position[wire].is_none() {
+            output_position[wire] = Some(k);
+        } else {
+            copies.push((k, wire));
+        }
+    }
+
+    let input_length = inputs.len();
+    let gate_count = gates.len() + copies.len();
+    let first_output = input_length + gate_count - outputs.len();
+
+    let mut map = vec![usize::MAX; wire_count];
+    for (i, &wire) in inputs.iter().enumerate() {
+        map[wire] = i;
+    }
+    let mut next = input_length;
+    let mut new_gates = Vec::with_capacity(gate_count);
+    for &(output, gate) in gates {
+        let gate = gate.map_inputs(|wire| map[wire]);
+        map[output] = match output_position[output] {
+            Some(k) => first_output + k,
+            None => {
+                next += 1;
+                next - 1
+            }
+        };
+        new_gates.push((map[output], gate));
+    }
+    new_gates.extend(copies.into_iter()
+        .map(|(k, wire)| (first_output + k, Gate::EqW(map[wire]))));
+
+    Circuit {
+        input_length,
+        output_length: outputs.len(),
+        input_groups,
+        output_groups,
+        gates: new_gates,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::circuit::{CircuitBuilder, Gate};
+
+    #[test]
+    fn test_build() {
+        let mut builder = CircuitBuilder::new();
+        let a = builder.inputs(2);
+        let b = builder.input();
+        let x = builder.xor(a[0], a[1]);
+        let y = builder.or(x, b);
+        let z = builder.not(y);
+        let m = builder.mux(b, a[0], a[1]);
+        builder.outputs(&[z, y]);
+        builder.outputs(&[a[1], m, y]);
+        let circuit = builder.build();
+
+        assert!(circuit.verify_topology());
+        assert_eq!(circuit.input_groups, [2, 1]);
+        assert_eq!(circuit.output_groups, [2, 3]);
+        assert_eq!(circuit.input_length + circuit.gates.len(), 13);
+        assert_eq!(circuit.gates[circuit.gates.len() - 2..], [(10, Gate::EqW(1)), (12, Gate::EqW(9))]);
+
+        for i in 0..8 {
+            let input = vec![i & 1 != 0, i & 2 != 0, i & 4 != 0];
+            let y = (input[0] ^ input[1]) | input[2];
+            let m = if input[2] { input[1] } else { input[0] };
+            assert_eq!(circuit.evaluate(input.clone()), [!y, y, input[1], m, y]);
+        }
+    }
+
+    #[test]
+    fn test_constant() {
+        let mut builder = CircuitBuilder::new();
+        let a = builder.input();
+        let one = builder.constant(true);
+        let x = builder.and(a, one);
+        builder.outputs(&[x, one]);
+        let circuit = builder.build();
+
+        assert!(circuit.verify_topology());
+        assert_eq!(circuit.evaluate(vec![false]), [false, true]);
+        assert_eq!(circuit.evaluate(vec![true]), [true, true]);
+    }
+}
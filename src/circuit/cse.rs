@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+
+use crate::circuit::{Circuit, Gate};
+use crate::circuit::builder::renumber;
+
+impl Circuit {
+    /// Merges gates that compute the same function of the same wires, returning the smaller
+    /// circuit and the number of gates removed.
+    ///
+    /// Operands of AND and XOR are ordered before comparing, copies are replaced by their
+    /// source and `!!x` by `x`. Inversions that are no longer read are left in place for
+    /// [`Circuit::eliminate_dead_gates`].
+    pub fn eliminate_common_subexpressions(&self) -> (Circuit, usize) {
+        let wire_count = self.input_length + self.gates.len();
+        let mut map = (0..wire_count).collect::<Vec<_>>();
+        let mut seen = HashMap::new();
+        // Source of every kept INV gate, by output.
+        let mut inverted = HashMap::new();
+        let mut gates = Vec::with_capacity(self.gates.len());
+
+        for &(output, gate) in self.gates.iter() {
+            let gate = match gate.map_inputs(|wire| map[wire]) {
+                Gate::EqW(a) => {
+                    map[output] = a;
+                    continue;
+                }
+                Gate::Inv(a) if inverted.contains_key(&a) => {
+                    map[output] = inverted[&a];
+                    continue;
+                }
+                Gate::And(a, b) => Gate::And(a.min(b), a.max(b)),
+                Gate::Xor(a, b) => Gate::Xor(a.min(b), a.max(b)),
+                gate => gate,
+            };
+            map[output] = *seen.entry(gate).or_insert_with(|| {
+                if let Gate::Inv(a) = gate {
+                    inverted.insert(output, a);
+                }
+                gates.push((output, gate));
+                output
+            });
+        }
+
+        let inputs = (0..self.input_length).collect::<Vec<_>>();
+        let outputs = (wire_count - self.output_length..wire_count)
+            .map(|wire| map[wire])
+            .collect::<Vec<_>>();
+        let circuit = renumber(
+            wire_count,
+            &inputs,
+            self.input_groups.clone(),
+            &gates,
+            &outputs,
+            self.output_groups.clone(),
+        );
+        let removed = self.gates.len().saturating_sub(circuit.gates.len());
+        (circuit, removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, thread_rng};
+
+    use crate::circuit::{Circuit, CircuitBuilder, Gate};
+
+    #[test]
+    fn test_merge() {
+        let mut builder = CircuitBuilder::new();
+        let a = builder.inputs(2);
+        let x = builder.and(a[0], a[1]);
+        let y = builder.and(a[1], a[0]);
+        let n = builder.not(x);
+        let nn = builder.not(n);
+        let z = builder.xor(nn, y);
+        let w = builder.xor(y, a[0]);
+        let one = builder.constant(true);
+        let also_one = builder.constant(true);
+        builder.outputs(&[z, w, one, also_one]);
+        let circuit = builder.build();
+
+        let (merged, removed) = circuit.eliminate_common_subexpressions();
+        assert!(merged.verify_topology());
+        assert_eq!(merged.stats().and_count, 1);
+        // The AND, the unused INV, two XORs and the constant plus a copy for the duplicate output.
+        assert_eq!(merged.gates.len(), 6);
+        assert_eq!(removed, circuit.gates.len() - 6);
+        assert_eq!(merged.gates[merged.gates.len() - 1], (7, Gate::EqW(6)));
+        for i in 0..4 {
+            let input = vec![i & 1 != 0, i & 2 != 0];
+            assert_eq!(merged.evaluate(input.clone()), circuit.evaluate(input));
+        }
+    }
+
+    #[test]
+    fn test_bundled_circuits() {
+        for text in [
+            include_str!("../../circuits/mult64.txt"),
+            include_str!("../../circuits/aes_128.txt"),
+        ] {
+            let circuit: Circuit = text.parse().unwrap();
+            let (merged, removed) = circuit.eliminate_common_subexpressions();
+            assert!(merged.verify_topology());
+            assert!(merged.stats().and_count <= circuit.stats().and_count);
+            assert_eq!(merged.eliminate_common_subexpressions().1, 0);
+            assert_eq!(removed, circuit.gates.len() - merged.gates.len());
+
+            let mut rng = thread_rng();
+            let input = (0..circuit.input_length).map(|_| rng.gen()).collect::<Vec<_>>();
+            assert_eq!(merged.evaluate(input.clone()), circuit.evaluate(input));
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use crate::circuit::Circuit;
+use crate::circuit::builder::renumber;
+
+impl Circuit {
+    /// Removes gates whose results never reach an output, returning the smaller circuit and
+    /// the number of gates removed.
+    ///
+    /// Wires are renumbered densely with the outputs still last. Inputs are always kept.
+    pub fn eliminate_dead_gates(&self) -> (Circuit, usize) {
+        let wire_count = self.input_length + self.gates.len();
+        let outputs = (wire_count - self.output_length..wire_count).collect::<Vec<_>>();
+
+        let mut live = vec![false; wire_count];
+        for &wire in &outputs {
+            live[wire] = true;
+        }
+        for &(output, gate) in self.gates.iter().rev() {
+            if live[output] {
+                for input in gate.inputs() {
+                    live[input] = true;
+                }
+            }
+        }
+
+        let gates = self.gates.iter()
+            .cloned()
+            .filter(|&(output, _)| live[output])
+            .collect::<Vec<_>>();
+        let inputs = (0..self.input_length).collect::<Vec<_>>();
+        let circuit = renumber(
+            wire_count,
+            &inputs,
+            self.input_groups.clone(),
+            &gates,
+            &outputs,
+            self.output_groups.clone(),
+        );
+        let removed = self.gates.len().saturating_sub(circuit.gates.len());
+        (circuit, removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, thread_rng};
+
+    use crate::circuit::{Circuit, CircuitBuilder, Gate};
+
+    #[test]
+    fn test_builder() {
+        let mut builder = CircuitBuilder::new();
+        let a = builder.inputs(3);
+        let x = builder.and(a[0], a[1]);
+        let unused = builder.xor(x, a[2]);
+        builder.not(unused);
+        let y = builder.xor(x, a[0]);
+        builder.output(y);
+        let circuit = builder.build();
+
+        let (eliminated, removed) = circuit.eliminate_dead_gates();
+        assert_eq!(removed, 2);
+        assert!(eliminated.verify_topology());
+        assert_eq!(eliminated.input_length, 3);
+        assert_eq!(eliminated.gates, [(3, Gate::And(0, 1)), (4, Gate::Xor(3, 0))]);
+    }
+
+    #[test]
+    fn test_bundled_circuits() {
+        for text in [
+            include_str!("../../circuits/adder64.txt"),
+            include_str!("../../circuits/sha256.txt"),
+        ] {
+            let circuit: Circuit = text.parse().unwrap();
+            let (eliminated, removed) = circuit.eliminate_dead_gates();
+            assert_eq!(removed, circuit.gates.len() - eliminated.gates.len());
+            assert!(eliminated.verify_topology());
+
+            let mut rng = thread_rng();
+            let input = (0..circuit.input_length).map(|_| rng.gen()).collect::<Vec<_>>();
+            assert_eq!(eliminated.evaluate(input.clone()), circuit.evaluate(input));
+        }
+    }
+
+    #[test]
+    fn test_after_folding() {
+        let mut builder = CircuitBuilder::new();
+        let a = builder.inputs(3);
+        let zero = builder.constant(false);
+        let x = builder.xor(a[0], a[1]);
+        let y = builder.and(x, zero);
+        let z = builder.xor(y, a[2]);
+        let w = builder.and(z, a[0]);
+        builder.outputs(&[z, w]);
+        let folded = builder.build().fold_constants();
+
+        let (eliminated, removed) = folded.eliminate_dead_gates();
+        assert_eq!(removed, 1);
+        assert_eq!(eliminated.gates, [(4, Gate::And(2, 0)), (3, Gate::EqW(2))]);
+        assert_eq!(eliminated.eliminate_dead_gates().1, 0);
+    }
+}
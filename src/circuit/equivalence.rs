@@ -0,0 +1,434 @@
+use std::collections::{HashMap, HashSet};
+
+use rand::{Rng, thread_rng};
+
+use crate::circuit::{BitSlice, Circuit, Gate};
+use crate::circuit::sat::{Lit, Solver};
+
+/// Rounds of 64 random inputs simulated before falling back to SAT.
+const SIMULATION_ROUNDS: usize = 16;
+/// Words of 64 random inputs simulated on every node to find candidate equal nodes.
+const SWEEP_WORDS: usize = 4;
+/// Conflicts spent on a single query while proving two nodes equal.
+const SWEEP_BUDGET: u64 = 200;
+/// Levels of fanin decided on when first trying to prove two nodes equal.
+const WINDOW_DEPTH: usize = 3;
+
+/// Result of [`Circuit::check_equivalence`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Equivalence {
+    Equivalent,
+    /// An input on which the two circuits produce different outputs.
+    Counterexample(Vec<bool>),
+}
+
+impl Circuit {
+    /// Checks whether both circuits compute the same function.
+    ///
+    /// Random bit-sliced simulation catches most differences cheaply. Otherwise both circuits
+    /// are merged into one graph while proving internal nodes equal with SAT, and the outputs
+    /// that remain distinct are compared by encoding their XOR to CNF and solving it, which
+    /// gives a definitive answer.
+    ///
+    /// # Panics
+    ///
+    /// If the circuits have different numbers of inputs or outputs.
+    pub fn check_equivalence(&self, other: &Circuit) -> Equivalence {
+        assert_eq!(self.input_length, other.input_length);
+        assert_eq!(self.output_length, other.output_length);
+
+        if let Some(input) = self.simulate_against(other) {
+            return Equivalence::Counterexample(input);
+        }
+
+        let mut miter = Miter::new(self.input_length);
+        let left = miter.translate(self);
+        let right = miter.translate(other);
+        match miter.solve(&left, &right) {
+            Some(input) => {
+                debug_assert_ne!(self.evaluate(input.clone()), other.evaluate(input.clone()));
+                Equivalence::Counterexample(input)
+            }
+            None => Equivalence::Equivalent,
+        }
+    }
+
+    fn simulate_against(&self, other: &Circuit) -> Option<Vec<bool>> {
+        let mut rng = thread_rng();
+        for _ in 0..SIMULATION_ROUNDS {
+            let input = (0..self.input_length).map(|_| rng.gen::<u64>()).collect::<Vec<_>>();
+            let difference = self.evaluate_sliced(&input).into_iter()
+                .zip(other.evaluate_sliced(&input))
+                .fold(0, |difference, (a, b)| difference | (a ^ b));
+            if difference != 0 {
+                let lane = difference.trailing_zeros() as usize;
+                return Some(input.iter().map(|word| word.lane(lane)).collect());
+            }
+        }
+        None
+    }
+}
+
+/// Variable 0 of a miter is the constant false.
+const FALSE: Lit = Lit::new(0, false);
+
+#[derive(Debug, Copy, Clone)]
+enum Node {
+    False,
+    Input,
+    And(Lit, Lit),
+    Xor(Lit, Lit),
+}
+
+/// Both circuits as one graph of AND and XOR nodes over shared inputs, where every node is
+/// merged into an earlier one if they are structurally identical or proven equal, known as
+/// SAT sweeping. Node `i` is variable `i` of the CNF in `solver`.
+struct Miter {
+    input_length: usize,
+    nodes: Vec<Node>,
+    hashed: HashMap<(bool, Lit, Lit), Lit>,
+    solver: Solver,
+    /// Simulated values of every node, 64 inputs per word. The first words are random and
+    /// the others hold one counterexample per lane.
+    words: Vec<Vec<u64>>,
+    counterexamples: Vec<Vec<bool>>,
+    /// Nodes not proven equal so far for every value of the random words, with whether their
+    /// values were complemented to start with a zero bit.
+    classes: HashMap<Vec<u64>, Vec<(usize, bool)>>,
+    /// Inputs every node depends on, as a bit set of `stride` words.
+    support: Vec<u64>,
+    stride: usize,
+}
+
+/// Outcome of trying to prove two nodes equal.
+enum Proof {
+    Equal,
+    Different(Vec<bool>),
+    Unknown,
+}
+
+impl Miter {
+    fn new(input_length: usize) -> Self {
+        let mut miter = Miter {
+            input_length,
+            nodes: vec![],
+            hashed: HashMap::new(),
+            solver: Solver::new(),
+            words: vec![vec![]; SWEEP_WORDS],
+            counterexamples: vec![],
+            classes: HashMap::new(),
+            support: vec![],
+            stride: input_length.div_ceil(64),
+        };
+        miter.push(Node::False);
+        miter.solver.add_clause(&[!FALSE]);
+        for _ in 0..input_length {
+            miter.push(Node::Input);
+        }
+        miter
+    }
+
+    /// Returns the literal of every output.
+    fn translate(&mut self, circuit: &Circuit) -> Vec<Lit> {
+        let wire_count = circuit.input_length + circuit.gates.len();
+        let mut map = (0..circuit.input_length)
+            .map(|wire| Lit::new(1 + wire, false))
+            .collect::<Vec<_>>();
+        map.resize(wire_count, FALSE);
+        for &(output, gate) in circuit.gates.iter() {
+            map[output] = match gate {
+                Gate::Eq(c) => Lit::new(0, c),
+                Gate::EqW(a) => map[a],
+                Gate::Inv(a) => !map[a],
+                Gate::And(a, b) => self.and(map[a], map[b]),
+                Gate::Xor(a, b) => self.xor(map[a], map[b]),
+            };
+        }
+        map.split_off(wire_count - circuit.output_length)
+    }
+
+    fn and(&mut self, a: Lit, b: Lit) -> Lit {
+        let (a, b) = (a.min(b), a.max(b));
+        if a == FALSE || a == !b {
+            return FALSE;
+        }
+        if a == !FALSE || a == b {
+            return b;
+        }
+        self.node(false, a, b)
+    }
+
+    fn xor(&mut self, a: Lit, b: Lit) -> Lit {
+        let negated = a.is_negated() ^ b.is_negated();
+        let (a, b) = (Lit::new(a.var(), false), Lit::new(b.var(), false));
+        let (a, b) = (a.min(b), a.max(b));
+        let lit = if a == b {
+            FALSE
+        } else if a == FALSE {
+            b
+        } else {
+            self.node(true, a, b)
+        };
+        if negated { !lit } else { lit }
+    }
+
+    fn node(&mut self, xor: bool, a: Lit, b: Lit) -> Lit {
+        if let Some(&lit) = self.hashed.get(&(xor, a, b)) {
+            return lit;
+        }
+        let lit = self.push(if xor { Node::Xor(a, b) } else { Node::And(a, b) });
+        self.hashed.insert((xor, a, b), lit);
+        lit
+    }
+
+    /// Adds a node and returns the literal it is equal to, which is its own unless it was
+    /// proven equal to an earlier node.
+    fn push(&mut self, node: Node) -> Lit {
+        let var = self.solver.new_var();
+        debug_assert_eq!(var, self.nodes.len());
+        self.solver.set_decision(var, false);
+        self.nodes.push(node);
+        let n = Lit::new(var, false);
+        match node {
+            Node::False | Node::Input => {}
+            Node::And(a, b) => {
+                self.solver.add_clause(&[!n, a]);
+                self.solver.add_clause(&[!n, b]);
+                self.solver.add_clause(&[n, !a, !b]);
+            }
+            Node::Xor(a, b) => encode_xor(&mut self.solver, n, a, b),
+        }
+
+        let mut rng = thread_rng();
+        for k in 0..self.words.len() {
+            let word = match node {
+                Node::Input if k < SWEEP_WORDS => rng.gen(),
+                node => self.evaluate(node, var, k),
+            };
+            self.words[k].push(word);
+        }
+        let stride = self.stride;
+        for k in 0..stride {
+            let word = match node {
+                Node::False => 0,
+                Node::Input => if (var - 1) / 64 == k { 1 << ((var - 1) % 64) } else { 0 },
+                Node::And(a, b) | Node::Xor(a, b) =>
+                    self.support[a.var() * stride + k] | self.support[b.var() * stride + k],
+            };
+            self.support.push(word);
+        }
+
+        let negated = self.words[0][var] & 1 != 0;
+        let key = self.words[..SWEEP_WORDS].iter()
+            .map(|word| if negated { !word[var] } else { word[var] })
+            .collect::<Vec<_>>();
+        loop {
+            let (words, counterexamples) = (&self.words, self.counterexamples.len());
+            let members = self.classes.entry(key.clone()).or_default();
+            let candidate = members.iter()
+                .find(|&&(other, flipped)| agree(words, counterexamples, var, other, negated ^ flipped));
+            let (other, flipped) = match candidate {
+                Some(&candidate) => candidate,
+                None => {
+                    members.push((var, negated));
+                    return n;
+                }
+            };
+            let target = Lit::new(other, negated ^ flipped);
+            match self.prove(n, target) {
+                Proof::Equal => {
+                    self.solver.add_clause(&[!n, target]);
+                    self.solver.add_clause(&[n, !target]);
+                    return target;
+                }
+                Proof::Different(input) => self.refine(input),
+                Proof::Unknown => return n,
+            }
+        }
+    }
+
+    /// Value of `node` at `var` in word `k`, from the words of its inputs, or of the last
+    /// counterexamples for inputs.
+    fn evaluate(&self, node: Node, var: usize, k: usize) -> u64 {
+        let value = |lit: Lit| self.words[k][lit.var()] ^ if lit.is_negated() { !0 } else { 0 };
+        match node {
+            Node::False => 0,
+            Node::Input => self.counterexamples[64 * (k - SWEEP_WORDS)..].iter()
+                .take(64)
+                .enumerate()
+                .fold(0, |word, (lane, input)| word | (input[var - 1] as u64) << lane),
+            Node::And(a, b) => value(a) & value(b),
+            Node::Xor(a, b) => value(a) ^ value(b),
+        }
+    }
+
+    /// Adds a counterexample to the simulated values of every node.
+    fn refine(&mut self, input: Vec<bool>) {
+        self.counterexamples.push(input);
+        let k = SWEEP_WORDS + (self.counterexamples.len() - 1) / 64;
+        if k == self.words.len() {
+            self.words.push(vec![]);
+        }
+        self.words[k].clear();
+        for var in 0..self.nodes.len() {
+            let word = self.evaluate(self.nodes[var], var, k);
+            self.words[k].push(word);
+        }
+    }
+
+    /// Tries to prove `a == b`, first deciding only on nearby nodes, which suffices when
+    /// their inputs were already merged, then on all inputs they depend on.
+    fn prove(&mut self, a: Lit, b: Lit) -> Proof {
+        let window = self.window(a.var(), b.var());
+        window.iter().for_each(|&var| self.solver.set_decision(var, true));
+        let local = self.query(a, b);
+        window.iter().for_each(|&var| self.solver.set_decision(var, false));
+        if local == Some(false) {
+            return Proof::Equal;
+        }
+
+        let inputs = (0..self.input_length)
+            .filter(|&i| {
+                let (word, bit) = (i / 64, 1 << (i % 64));
+                (self.support[a.var() * self.stride + word] | self.support[b.var() * self.stride + word]) & bit != 0
+            })
+            .map(|i| 1 + i)
+            .collect::<Vec<_>>();
+        inputs.iter().for_each(|&var| self.solver.set_decision(var, true));
+        let result = self.query(a, b);
+        let input = (1..=self.input_length).map(|var| self.solver.model(var)).collect();
+        inputs.iter().for_each(|&var| self.solver.set_decision(var, false));
+        match result {
+            Some(false) => Proof::Equal,
+            Some(true) => Proof::Different(input),
+            None => Proof::Unknown,
+        }
+    }
+
+    /// Searches for an assignment with `a != b`.
+    fn query(&mut self, a: Lit, b: Lit) -> Option<bool> {
+        match self.solver.solve_limited(&[a, !b], Some(SWEEP_BUDGET)) {
+            Some(false) => self.solver.solve_limited(&[!a, b], Some(SWEEP_BUDGET)),
+            result => result,
+        }
+    }
+
+    /// Nodes at most `WINDOW_DEPTH` levels of fanin away from `a` or `b`.
+    fn window(&self, a: usize, b: usize) -> Vec<usize> {
+        let mut window = HashSet::new();
+        let mut level = vec![a, b];
+        for _ in 0..=WINDOW_DEPTH {
+            let mut next = vec![];
+            for var in level {
+                if var != 0 && window.insert(var) {
+                    if let Node::And(x, y) | Node::Xor(x, y) = self.nodes[var] {
+                        next.extend([x.var(), y.var()]);
+                    }
+                }
+            }
+            level = next;
+        }
+        window.into_iter().collect()
+    }
+
+    /// Searches for an input on which some pair of outputs differs.
+    fn solve(mut self, left: &[Lit], right: &[Lit]) -> Option<Vec<bool>> {
+        let pairs = left.iter().cloned()
+            .zip(right.iter().cloned())
+            .filter(|(a, b)| a != b)
+            .collect::<Vec<_>>();
+        if pairs.is_empty() {
+            return None;
+        }
+
+        let solver = &mut self.solver;
+        let differences = pairs.iter()
+            .map(|&(a, b)| {
+                let d = Lit::new(solver.new_var(), false);
+                solver.set_decision(d.var(), false);
+                encode_xor(solver, d, a, b);
+                d
+            })
+            .collect::<Vec<_>>();
+        solver.add_clause(&differences);
+
+        // Every other variable is implied by the inputs, so only they are decided.
+        (1..=self.input_length).for_each(|var| solver.set_decision(var, true));
+        if solver.solve() {
+            Some((1..=self.input_length).map(|var| solver.model(var)).collect())
+        } else {
+            None
+        }
+    }
+}
+
+/// Whether nodes `a` and `b`, complemented if `negated`, agree on every counterexample.
+fn agree(words: &[Vec<u64>], counterexamples: usize, a: usize, b: usize, negated: bool) -> bool {
+    words[SWEEP_WORDS..].iter().enumerate().all(|(i, word)| {
+        let lanes = (counterexamples - 64 * i).min(64);
+        let mask = if lanes == 64 { !0 } else { (1 << lanes) - 1 };
+        (word[a] ^ word[b] ^ if negated { !0 } else { 0 }) & mask == 0
+    })
+}
+
+/// Adds the clauses of `n = a ^ b`.
+fn encode_xor(solver: &mut Solver, n: Lit, a: Lit, b: Lit) {
+    solver.add_clause(&[!n, a, b]);
+    solver.add_clause(&[!n, !a, !b]);
+    solver.add_clause(&[n, !a, b]);
+    solver.add_clause(&[n, a, !b]);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::circuit::{Circuit, CircuitBuilder, Equivalence, gadgets};
+
+    fn adder(lookahead: bool) -> Circuit {
+        let mut b = CircuitBuilder::new();
+        let x = b.inputs(16);
+        let y = b.inputs(16);
+        let sum = if lookahead { gadgets::add_lookahead(&mut b, &x, &y) } else { gadgets::add(&mut b, &x, &y) };
+        b.outputs(&sum);
+        b.build()
+    }
+
+    #[test]
+    fn test_adders() {
+        assert_eq!(adder(false).check_equivalence(&adder(true)), Equivalence::Equivalent);
+    }
+
+    #[test]
+    fn test_rewritten() {
+        let circuit: Circuit = include_str!("../../circuits/aes_128.txt").parse().unwrap();
+        let (optimized, _) = circuit.absorb_inversions().eliminate_common_subexpressions();
+        assert_eq!(circuit.check_equivalence(&optimized), Equivalence::Equivalent);
+    }
+
+    #[test]
+    fn test_counterexample() {
+        // Equal to a constant, which random simulation is very unlikely to hit.
+        let build = |target: u32| {
+            let mut b = CircuitBuilder::new();
+            let x = b.inputs(32);
+            let c = (0..32).map(|i| b.constant(target >> i & 1 != 0)).collect::<Vec<_>>();
+            let eq = gadgets::equal(&mut b, &x, &c);
+            b.output(eq);
+            b.build()
+        };
+        let never = {
+            let mut b = CircuitBuilder::new();
+            b.inputs(32);
+            let zero = b.constant(false);
+            b.output(zero);
+            b.build()
+        };
+        let target = 0x9e37_79b9;
+        match build(target).check_equivalence(&never) {
+            Equivalence::Counterexample(input) => {
+                let value = input.iter().rev().fold(0u32, |v, &bit| v << 1 | bit as u32);
+                assert_eq!(value, target);
+            }
+            Equivalence::Equivalent => panic!("Circuits differ"),
+        }
+    }
+}
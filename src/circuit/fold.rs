@@ -0,0 +1,170 @@
+use crate::circuit::{Circuit, Gate};
+use crate::circuit::builder::renumber;
+
+/// How a gate behaves once the values of public constant wires are known.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum Resolved {
+    /// The output has a public value.
+    Public(bool),
+    /// The output equals the given wire.
+    Copy(usize),
+    /// The output is the negation of the given wire.
+    Invert(usize),
+    /// None of the inputs are public, so the gate must be evaluated.
+    Gate,
+}
+
+impl Circuit {
+    /// Resolves every gate against the constants introduced by EQ gates, for the garbling
+    /// schemes to handle such gates without ciphertexts.
+    pub(crate) fn resolve_constants(&self) -> Vec<Resolved> {
+        let mut public = vec![None; self.input_length + self.gates.len()];
+        self.gates.iter()
+            .map(|&(output, gate)| {
+                let resolved = match gate {
+                    Gate::Eq(c) => Resolved::Public(c),
+                    Gate::EqW(a) => public[a].map_or(Resolved::Gate, Resolved::Public),
+                    Gate::Inv(a) => public[a].map_or(Resolved::Gate, |v: bool| Resolved::Public(!v)),
+                    Gate::And(a, b) | Gate::Xor(a, b) => {
+                        let and = matches!(gate, Gate::And(_, _));
+                        match (public[a], public[b]) {
+                            (Some(x), Some(y)) => Resolved::Public(if and { x & y } else { x ^ y }),
+                            (Some(x), None) => resolve_binary(and, x, b),
+                            (None, Some(y)) => resolve_binary(and, y, a),
+                            _ if a == b => if and { Resolved::Copy(a) } else { Resolved::Public(false) },
+                            _ => Resolved::Gate,
+                        }
+                    }
+                };
+                if let Resolved::Public(value) = resolved {
+                    public[output] = Some(value);
+                }
+                resolved
+            })
+            .collect()
+    }
+
+    /// Propagates constants through the circuit, removing gates with public outputs and
+    /// replacing gates with a single public input by copies or inversions.
+    ///
+    /// Gates that only fed removed gates are left in place for [`Circuit::eliminate_dead_gates`].
+    pub fn fold_constants(&self) -> Circuit {
+        #[derive(Copy, Clone)]
+        enum Folded {
+            Constant(bool),
+            Wire(usize),
+        }
+
+        let wire_count = self.input_length + self.gates.len();
+        let mut map = (0..wire_count).map(Folded::Wire).collect::<Vec<_>>();
+        let mut gates = vec![];
+        let mut next = self.input_length;
+        let mut emit = |gates: &mut Vec<(usize, Gate)>, gate| {
+            gates.push((next, gate));
+            next += 1;
+            next - 1
+        };
+        let wire = |map: &[Folded], a: usize| match map[a] {
+            Folded::Wire(w) => w,
+            Folded::Constant(_) => unreachable!("resolved gates only read secret wires"),
+        };
+
+        for (&(output, gate), resolved) in self.gates.iter().zip(self.resolve_constants()) {
+            map[output] = match resolved {
+                Resolved::Public(value) => Folded::Constant(value),
+                Resolved::Copy(a) => map[a],
+                Resolved::Invert(a) => Folded::Wire(emit(&mut gates, Gate::Inv(wire(&map, a)))),
+                Resolved::Gate => match gate {
+                    Gate::EqW(a) => map[a],
+                    gate => Folded::Wire(emit(&mut gates, gate.map_inputs(|a| wire(&map, a)))),
+                },
+            };
+        }
+
+        let outputs = (wire_count - self.output_length..wire_count)
+            .map(|w| match map[w] {
+                Folded::Wire(w) => w,
+                Folded::Constant(value) => emit(&mut gates, Gate::Eq(value)),
+            })
+            .collect::<Vec<_>>();
+        let inputs = (0..self.input_length).collect::<Vec<_>>();
+        renumber(next, &inputs, self.input_groups.clone(), &gates, &outputs, self.output_groups.clone())
+    }
+
+    /// Turns input group `group` into public constants holding `values`.
+    ///
+    /// The remaining input groups keep their order. Garbling the result spends no ciphertexts
+    /// on gates whose outcome follows from the constants.
+    pub fn with_public_input(&self, group: usize, values: &[bool]) -> Circuit {
+        let range = self.input_range(group);
+        assert_eq!(range.len(), values.len());
+
+        let inputs = (0..self.input_length)
+            .filter(|i| !range.contains(i))
+            .collect::<Vec<_>>();
+        let mut input_groups = self.input_groups.clone();
+        input_groups.remove(group);
+        let gates = range.clone()
+            .zip(values)
+            .map(|(wire, &value)| (wire, Gate::Eq(value)))
+            .chain(self.gates.iter().cloned())
+            .collect::<Vec<_>>();
+        let wire_count = self.input_length + self.gates.len();
+        let outputs = (wire_count - self.output_length..wire_count).collect::<Vec<_>>();
+        renumber(wire_count, &inputs, input_groups, &gates, &outputs, self.output_groups.clone())
+    }
+}
+
+fn resolve_binary(and: bool, value: bool, other: usize) -> Resolved {
+    match (and, value) {
+        (true, false) => Resolved::Public(false),
+        (true, true) | (false, false) => Resolved::Copy(other),
+        (false, true) => Resolved::Invert(other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, thread_rng};
+
+    use crate::circuit::{Circuit, Gate};
+    use crate::garbled_circuit::classic::Classic;
+    use crate::garbled_circuit::half_gates::HalfGates;
+    use crate::util::u8_to_bits;
+
+    #[test]
+    fn test_fold() {
+        // out0 = (a & 1) ^ 1, out1 = b ^ b, out2 = (a & 0) ^ b
+        let circuit: Circuit = "7 9\n1 2\n1 3\n\n1 1 1 2 EQ\n1 1 0 3 EQ\n2 1 0 2 4 AND\n2 1 4 2 6 XOR\n\
+            2 1 1 1 7 XOR\n2 1 0 3 5 AND\n2 1 5 1 8 XOR\n".parse().unwrap();
+        let folded = circuit.fold_constants();
+        assert!(folded.verify_topology());
+        assert_eq!(folded.gates, [(2, Gate::Inv(0)), (3, Gate::Eq(false)), (4, Gate::EqW(1))]);
+        for i in 0..4 {
+            let input = vec![i & 1 != 0, i & 2 != 0];
+            assert_eq!(folded.evaluate(input.clone()), circuit.evaluate(input));
+        }
+    }
+
+    #[test]
+    fn test_public_input() {
+        let circuit: Circuit = include_str!("../../circuits/aes_128.txt").parse().unwrap();
+        let key = (0..16).flat_map(|i| u8_to_bits(i * 17)).collect::<Vec<_>>();
+        let public = circuit.with_public_input(0, &key);
+        assert!(public.verify_topology());
+        assert_eq!(public.input_groups, [128]);
+
+        let folded = public.fold_constants();
+        assert!(folded.stats().and_count < circuit.stats().and_count);
+        assert_eq!(public.estimated_size::<HalfGates, _, _>(), folded.estimated_size::<HalfGates, _, _>());
+        assert_eq!(public.estimated_size::<Classic, _, _>(), folded.estimated_size::<Classic, _, _>());
+
+        let mut rng = thread_rng();
+        for _ in 0..4 {
+            let msg = (0..128).map(|_| rng.gen()).collect::<Vec<_>>();
+            let expected = circuit.evaluate([key.clone(), msg.clone()].concat());
+            assert_eq!(public.evaluate(msg.clone()), expected);
+            assert_eq!(folded.evaluate(msg), expected);
+        }
+    }
+}
@@ -0,0 +1,538 @@
+//! Word-level gadgets built on [`CircuitBuilder`].
+//!
+//! Words are little-endian slices of wires, i.e. `x[0]` is the least significant bit. Unless
+//! noted otherwise arithmetic wraps around at the width of the inputs, which must be equal.
+//! The constructions aim for few AND gates since XOR and INV gates are free to garble.
+
+use crate::circuit::{CircuitBuilder, Wire};
+
+/// Sum and carry of `x + y + c` using a single AND gate.
+pub fn full_adder(b: &mut CircuitBuilder, x: Wire, y: Wire, c: Wire) -> (Wire, Wire) {
+    let xc = b.xor(x, c);
+    let yc = b.xor(y, c);
+    let sum = b.xor(xc, y);
+    let t = b.and(xc, yc);
+    (sum, b.xor(t, c))
+}
+
+/// `x + y` with the carry out as an extra most significant bit.
+///
+/// The inputs may have different widths; the result is one bit wider than the widest.
+pub fn add_full(b: &mut CircuitBuilder, x: &[Wire], y: &[Wire]) -> Vec<Wire> {
+    let (long, short) = if x.len() >= y.len() { (x, y) } else { (y, x) };
+    let mut sum = Vec::with_capacity(long.len() + 1);
+    let mut carry = None;
+    for (i, &a) in long.iter().enumerate() {
+        let (s, c) = match (short.get(i), carry) {
+            (Some(&d), Some(c)) => full_adder(b, a, d, c),
+            (Some(&d), None) => (b.xor(a, d), b.and(a, d)),
+            (None, Some(c)) => (b.xor(a, c), b.and(a, c)),
+            (None, None) => {
+                sum.push(a);
+                continue;
+            }
+        };
+        sum.push(s);
+        carry = Some(c);
+    }
+    sum.extend(carry);
+    sum
+}
+
+/// Ripple-carry `x + y` using one AND gate per bit.
+pub fn add(b: &mut CircuitBuilder, x: &[Wire], y: &[Wire]) -> Vec<Wire> {
+    assert_eq!(x.len(), y.len());
+    if x.is_empty() {
+        return vec![];
+    }
+    let mut sum = add_full(b, &x[..x.len() - 1], &y[..y.len() - 1]);
+    let carry = sum.pop();
+    let top = b.xor(x[x.len() - 1], y[y.len() - 1]);
+    sum.push(match carry {
+        Some(c) => b.xor(top, c),
+        None => top,
+    });
+    sum
+}
+
+/// Adds `y` into `x`, where `y` may be narrower than `x`, dropping the carry out.
+fn add_into(b: &mut CircuitBuilder, x: &[Wire], y: &[Wire]) -> Vec<Wire> {
+    assert!(y.len() <= x.len());
+    let n = x.len();
+    if n == 0 {
+        return vec![];
+    }
+    let mut sum = add_full(b, &x[..n - 1], &y[..y.len().min(n - 1)]);
+    let carry = if sum.len() == n { sum.pop() } else { None };
+    let mut top = x[n - 1];
+    if y.len() == n {
+        top = b.xor(top, y[n - 1]);
+    }
+    sum.push(match carry {
+        Some(c) => b.xor(top, c),
+        None => top,
+    });
+    sum
+}
+
+/// Carry-lookahead `x + y` using a Sklansky parallel prefix network.
+///
+/// The depth is logarithmic in the width at the cost of more AND gates than [`add`].
+pub fn add_lookahead(b: &mut CircuitBuilder, x: &[Wire], y: &[Wire]) -> Vec<Wire> {
+    assert_eq!(x.len(), y.len());
+    let n = x.len();
+    let propagate = x.iter().zip(y).map(|(&a, &c)| b.xor(a, c)).collect::<Vec<_>>();
+    // Generate and propagate are mutually exclusive, so `g | p & g'` can use XOR instead of OR.
+    let mut group = x.iter().zip(y).zip(&propagate)
+        .map(|((&a, &c), &p)| (b.and(a, c), p))
+        .collect::<Vec<_>>();
+    let mut span = 1;
+    while span < n {
+        for i in (0..n).filter(|i| i & span != 0) {
+            // Combine with the last position of the lower half of the block.
+            let start = i & !(2 * span - 1);
+            let (g, p) = group[i];
+            let (gj, pj) = group[start + span - 1];
+            let t = b.and(p, gj);
+            // Propagate is never needed for prefixes that start at bit 0.
+            let p = if start != 0 { b.and(p, pj) } else { p };
+            group[i] = (b.xor(g, t), p);
+        }
+        span *= 2;
+    }
+    (0..n)
+        .map(|i| if i == 0 { propagate[0] } else { b.xor(propagate[i], group[i - 1].0) })
+        .collect()
+}
+
+/// `x - y` along with whether it borrowed, i.e. whether `x < y`.
+///
+/// The result has the width of `x`; `y` may be narrower or wider.
+pub fn sub_with_borrow(b: &mut CircuitBuilder, x: &[Wire], y: &[Wire]) -> (Vec<Wire>, Wire) {
+    let mut diff = Vec::with_capacity(x.len());
+    let mut borrow: Option<Wire> = None;
+    for i in 0..x.len().max(y.len()) {
+        let next = match (x.get(i), y.get(i), borrow) {
+            (Some(&a), Some(&c), Some(w)) => {
+                // borrow' = maj(!a, c, w) = w ^ (!(a ^ w) & (c ^ w))
+                let aw = b.xor(a, w);
+                diff.push(b.xor(aw, c));
+                let naw = b.not(aw);
+                let cw = b.xor(c, w);
+                let t = b.and(naw, cw);
+                b.xor(t, w)
+            }
+            (Some(&a), Some(&c), None) => {
+                diff.push(b.xor(a, c));
+                let na = b.not(a);
+                b.and(na, c)
+            }
+            (Some(&a), None, Some(w)) => {
+                diff.push(b.xor(a, w));
+                let na = b.not(a);
+                b.and(na, w)
+            }
+            (Some(&a), None, None) => {
+                diff.push(a);
+                continue;
+            }
+            (None, Some(&c), Some(w)) => b.or(c, w),
+            (None, Some(&c), None) => c,
+            (None, None, _) => unreachable!(),
+        };
+        borrow = Some(next);
+    }
+    let borrow = match borrow {
+        Some(w) => w,
+        None => b.constant(false),
+    };
+    (diff, borrow)
+}
+
+/// `x - y` using one AND gate per bit.
+pub fn sub(b: &mut CircuitBuilder, x: &[Wire], y: &[Wire]) -> Vec<Wire> {
+    assert_eq!(x.len(), y.len());
+    if x.is_empty() {
+        return vec![];
+    }
+    let n = x.len();
+    let (mut diff, borrow) = if n > 1 {
+        let (diff, borrow) = sub_with_borrow(b, &x[..n - 1], &y[..n - 1]);
+        (diff, Some(borrow))
+    } else {
+        (vec![], None)
+    };
+    let top = b.xor(x[n - 1], y[n - 1]);
+    diff.push(match borrow {
+        Some(w) => b.xor(top, w),
+        None => top,
+    });
+    diff
+}
+
+/// Subtracts `y` from `x`, where `y` may be narrower than `x`, dropping the borrow out.
+fn sub_from(b: &mut CircuitBuilder, x: &[Wire], y: &[Wire]) -> Vec<Wire> {
+    assert!(y.len() <= x.len());
+    if y.len() == x.len() {
+        return sub(b, x, y);
+    }
+    sub_with_borrow(b, x, y).0
+}
+
+/// Two's complement negation `-x`, using one AND gate per bit.
+pub fn negate(b: &mut CircuitBuilder, x: &[Wire]) -> Vec<Wire> {
+    // Bits above the lowest set bit are flipped.
+    let mut result = Vec::with_capacity(x.len());
+    let mut seen: Option<Wire> = None;
+    for (i, &a) in x.iter().enumerate() {
+        result.push(match seen {
+            Some(s) => b.xor(a, s),
+            None => a,
+        });
+        if i + 1 < x.len() {
+            seen = Some(match seen {
+                Some(s) => b.or(s, a),
+                None => a,
+            });
+        }
+    }
+    result
+}
+
+/// Unsigned `x < y`.
+pub fn less_than(b: &mut CircuitBuilder, x: &[Wire], y: &[Wire]) -> Wire {
+    assert_eq!(x.len(), y.len());
+    sub_with_borrow(b, x, y).1
+}
+
+/// Unsigned `x <= y`.
+pub fn less_equal(b: &mut CircuitBuilder, x: &[Wire], y: &[Wire]) -> Wire {
+    let greater = less_than(b, y, x);
+    b.not(greater)
+}
+
+/// Flips the sign bit, mapping two's complement order onto unsigned order.
+fn flip_sign(b: &mut CircuitBuilder, x: &[Wire]) -> Vec<Wire> {
+    let mut x = x.to_vec();
+    if let Some(top) = x.last_mut() {
+        *top = b.not(*top);
+    }
+    x
+}
+
+/// Signed (two's complement) `x < y`.
+pub fn signed_less_than(b: &mut CircuitBuilder, x: &[Wire], y: &[Wire]) -> Wire {
+    let x = flip_sign(b, x);
+    let y = flip_sign(b, y);
+    less_than(b, &x, &y)
+}
+
+/// Signed (two's complement) `x <= y`.
+pub fn signed_less_equal(b: &mut CircuitBuilder, x: &[Wire], y: &[Wire]) -> Wire {
+    let greater = signed_less_than(b, y, x);
+    b.not(greater)
+}
+
+/// Whether all of `x` is set, using a balanced tree of AND gates.
+pub fn all(b: &mut CircuitBuilder, x: &[Wire]) -> Wire {
+    match x.len() {
+        0 => b.constant(true),
+        1 => x[0],
+        n => {
+            let left = all(b, &x[..n / 2]);
+            let right = all(b, &x[n / 2..]);
+            b.and(left, right)
+        }
+    }
+}
+
+/// `x == y`.
+pub fn equal(b: &mut CircuitBuilder, x: &[Wire], y: &[Wire]) -> Wire {
+    assert_eq!(x.len(), y.len());
+    let same = x.iter().zip(y)
+        .map(|(&a, &c)| {
+            let d = b.xor(a, c);
+            b.not(d)
+        })
+        .collect::<Vec<_>>();
+    all(b, &same)
+}
+
+/// Returns `y` if `select` is set and `x` otherwise.
+pub fn mux(b: &mut CircuitBuilder, select: Wire, x: &[Wire], y: &[Wire]) -> Vec<Wire> {
+    assert_eq!(x.len(), y.len());
+    x.iter().zip(y)
+        .map(|(&a, &c)| b.mux(select, a, c))
+        .collect()
+}
+
+/// Unsigned minimum.
+pub fn min(b: &mut CircuitBuilder, x: &[Wire], y: &[Wire]) -> Vec<Wire> {
+    let lt = less_than(b, x, y);
+    mux(b, lt, y, x)
+}
+
+/// Unsigned maximum.
+pub fn max(b: &mut CircuitBuilder, x: &[Wire], y: &[Wire]) -> Vec<Wire> {
+    let lt = less_than(b, x, y);
+    mux(b, lt, x, y)
+}
+
+/// Barrel shifter shared by the shifts; `fill` provides the bit shifted in.
+fn shift<F>(b: &mut CircuitBuilder, x: &[Wire], amount: &[Wire], left: bool, mut fill: F) -> Vec<Wire>
+    where F: FnMut(&mut CircuitBuilder, Wire, Wire) -> Wire {
+    let n = x.len();
+    let mut x = x.to_vec();
+    for (k, &s) in amount.iter().enumerate() {
+        let distance = 1usize.checked_shl(k as u32).unwrap_or(usize::MAX);
+        x = (0..n)
+            .map(|i| {
+                let source = if left { i.checked_sub(distance) } else { i.checked_add(distance).filter(|&j| j < n) };
+                match source {
+                    Some(j) => b.mux(s, x[i], x[j]),
+                    None => fill(b, s, x[i]),
+                }
+            })
+            .collect();
+    }
+    x
+}
+
+/// Logical `x << amount`, where `amount` is an unsigned word of any width.
+pub fn shift_left(b: &mut CircuitBuilder, x: &[Wire], amount: &[Wire]) -> Vec<Wire> {
+    shift(b, x, amount, true, |b, s, a| {
+        let ns = b.not(s);
+        b.and(a, ns)
+    })
+}
+
+/// Logical `x >> amount`, where `amount` is an unsigned word of any width.
+pub fn shift_right(b: &mut CircuitBuilder, x: &[Wire], amount: &[Wire]) -> Vec<Wire> {
+    shift(b, x, amount, false, |b, s, a| {
+        let ns = b.not(s);
+        b.and(a, ns)
+    })
+}
+
+/// Arithmetic `x >> amount`, shifting in copies of the sign bit.
+pub fn shift_right_arithmetic(b: &mut CircuitBuilder, x: &[Wire], amount: &[Wire]) -> Vec<Wire> {
+    let sign = match x.last() {
+        Some(&sign) => sign,
+        None => return vec![],
+    };
+    shift(b, x, amount, false, |b, s, a| b.mux(s, a, sign))
+}
+
+/// Schoolbook `x * y` keeping the full `x.len() + y.len()` bit product.
+fn mul_schoolbook(b: &mut CircuitBuilder, x: &[Wire], y: &[Wire]) -> Vec<Wire> {
+    let mut product = Vec::with_capacity(x.len() + y.len());
+    let mut acc: Vec<Wire> = vec![];
+    for &c in y {
+        let row = x.iter().map(|&a| b.and(a, c)).collect::<Vec<_>>();
+        let mut sum = add_full(b, &acc, &row).into_iter();
+        product.extend(sum.next());
+        acc = sum.collect();
+    }
+    product.extend(acc);
+    product.truncate(x.len() + y.len());
+    product
+}
+
+/// Karatsuba `x * y` keeping the full `x.len() + y.len()` bit product.
+///
+/// Falls back to schoolbook multiplication for narrow operands where that uses fewer gates.
+pub fn mul_karatsuba(b: &mut CircuitBuilder, x: &[Wire], y: &[Wire]) -> Vec<Wire> {
+    const THRESHOLD: usize = 16;
+    let width = x.len() + y.len();
+    if x.len().min(y.len()) < THRESHOLD {
+        return mul_schoolbook(b, x, y);
+    }
+
+    let h = x.len().min(y.len()) / 2;
+    let (x0, x1) = x.split_at(h);
+    let (y0, y1) = y.split_at(h);
+    let z0 = mul_karatsuba(b, x0, y0);
+    let z2 = mul_karatsuba(b, x1, y1);
+    let sx = add_full(b, x0, x1);
+    let sy = add_full(b, y0, y1);
+    let z1 = mul_karatsuba(b, &sx, &sy);
+    // x0 * y1 + x1 * y0
+    let mid = sub_from(b, &z1, &z0);
+    let mid = sub_from(b, &mid, &z2);
+
+    let mut product = z0;
+    product.extend(z2);
+    let mid = &mid[..mid.len().min(width - h)];
+    let high = add_into(b, &product[h..], mid);
+    product.truncate(h);
+    product.extend(high);
+    product
+}
+
+/// `x * y` truncated to the width of the inputs, as in `mult64.txt`.
+pub fn mul(b: &mut CircuitBuilder, x: &[Wire], y: &[Wire]) -> Vec<Wire> {
+    assert_eq!(x.len(), y.len());
+    let n = x.len();
+    let mut product = Vec::with_capacity(n);
+    let mut acc: Vec<Wire> = vec![];
+    for (i, &c) in y.iter().enumerate() {
+        let row = x[..n - i].iter().map(|&a| b.and(a, c)).collect::<Vec<_>>();
+        let sum = if acc.is_empty() { row } else { add(b, &acc, &row) };
+        product.push(sum[0]);
+        acc = sum[1..].to_vec();
+    }
+    product
+}
+
+/// Unsigned restoring division returning the quotient and remainder.
+///
+/// Division by zero yields a quotient of all ones and a remainder of `x`.
+pub fn div_rem(b: &mut CircuitBuilder, x: &[Wire], y: &[Wire]) -> (Vec<Wire>, Vec<Wire>) {
+    assert_eq!(x.len(), y.len());
+    let mut quotient = Vec::with_capacity(x.len());
+    let mut remainder: Vec<Wire> = vec![];
+    for &bit in x.iter().rev() {
+        remainder.insert(0, bit);
+        let (diff, borrow) = sub_with_borrow(b, &remainder, y);
+        remainder = mux(b, borrow, &diff, &remainder);
+        // The restored remainder is below `y`, so it fits in the width of the inputs.
+        remainder.truncate(x.len());
+        quotient.push(b.not(borrow));
+    }
+    quotient.reverse();
+    (quotient, remainder)
+}
+
+/// Number of set bits in `x`, as a word of `floor(log2(x.len())) + 1` bits.
+pub fn popcount(b: &mut CircuitBuilder, x: &[Wire]) -> Vec<Wire> {
+    let mut counts = x.iter().map(|&w| vec![w]).collect::<Vec<_>>();
+    while counts.len() > 1 {
+        counts = counts.chunks(2)
+            .map(|pair| match pair {
+                [a, c] => add_full(b, a, c),
+                [a] => a.clone(),
+                _ => unreachable!(),
+            })
+            .collect();
+    }
+    let mut count = counts.pop().unwrap_or_default();
+    let width = usize::BITS as usize - x.len().leading_zeros() as usize;
+    count.truncate(width);
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, thread_rng};
+
+    use crate::circuit::{Circuit, CircuitBuilder, Wire};
+    use crate::circuit::gadgets::*;
+    use crate::util::{bits_to_u64, u64_to_bits};
+
+    fn binop<F>(f: F) -> Circuit
+        where F: FnOnce(&mut CircuitBuilder, &[Wire], &[Wire]) -> Vec<Wire> {
+        let mut b = CircuitBuilder::new();
+        let x = b.inputs(64);
+        let y = b.inputs(64);
+        let z = f(&mut b, &x, &y);
+        b.outputs(&z);
+        b.build()
+    }
+
+    fn evaluate(circuit: &Circuit, x: u64, y: u64) -> Vec<bool> {
+        circuit.evaluate([u64_to_bits(x), u64_to_bits(y)].concat())
+    }
+
+    fn assert_same(circuit: &Circuit, reference: &Circuit, nonzero: bool) {
+        assert!(circuit.verify_topology());
+        let mut rng = thread_rng();
+        for _ in 0..64 {
+            let x = rng.gen::<u64>() >> rng.gen_range(0..64);
+            let y = (rng.gen::<u64>() >> rng.gen_range(0..64)) | nonzero as u64;
+            assert_eq!(evaluate(circuit, x, y), evaluate(reference, x, y), "{} {}", x, y);
+        }
+    }
+
+    #[test]
+    fn test_adders() {
+        let reference: Circuit = include_str!("../../circuits/adder64.txt").parse().unwrap();
+        let ripple = binop(add);
+        assert_same(&ripple, &reference, false);
+        assert_eq!(ripple.stats().and_count, 63);
+        assert_same(&binop(add_lookahead), &reference, false);
+        assert_eq!(binop(add_lookahead).stats().and_depth, 7);
+    }
+
+    #[test]
+    fn test_sub() {
+        let reference: Circuit = include_str!("../../circuits/subtract64.txt").parse().unwrap();
+        let circuit = binop(sub);
+        assert_same(&circuit, &reference, false);
+        assert_eq!(circuit.stats().and_count, 63);
+    }
+
+    #[test]
+    fn test_mul() {
+        let reference: Circuit = include_str!("../../circuits/mult64.txt").parse().unwrap();
+        let circuit = binop(mul);
+        assert_same(&circuit, &reference, false);
+        assert!(circuit.stats().and_count <= reference.stats().and_count);
+
+        assert_same(&binop(|b, x, y| mul_karatsuba(b, x, y)[..64].to_vec()), &reference, false);
+        let karatsuba = binop(mul_karatsuba);
+        let mut rng = thread_rng();
+        for _ in 0..16 {
+            let (x, y) = (rng.gen::<u64>(), rng.gen::<u64>());
+            let product = evaluate(&karatsuba, x, y);
+            let expected = x as u128 * y as u128;
+            assert_eq!(bits_to_u64(&product[..64]), expected as u64);
+            assert_eq!(bits_to_u64(&product[64..]), (expected >> 64) as u64);
+        }
+    }
+
+    #[test]
+    fn test_div() {
+        let reference: Circuit = include_str!("../../circuits/udivide64.txt").parse().unwrap();
+        assert_same(&binop(|b, x, y| div_rem(b, x, y).0), &reference, true);
+
+        let remainder = binop(|b, x, y| div_rem(b, x, y).1);
+        assert_eq!(bits_to_u64(&evaluate(&remainder, 258290865, 165465)), 258290865 % 165465);
+    }
+
+    #[test]
+    fn test_word_operations() {
+        let circuit = binop(|b, x, y| {
+            let mut out = negate(b, x);
+            out.push(less_than(b, x, y));
+            out.push(less_equal(b, x, y));
+            out.push(signed_less_than(b, x, y));
+            out.push(signed_less_equal(b, x, y));
+            out.push(equal(b, x, y));
+            out.extend(min(b, x, y));
+            out.extend(max(b, x, y));
+            out.extend(shift_left(b, x, &y[..7]));
+            out.extend(shift_right(b, x, &y[..7]));
+            out.extend(shift_right_arithmetic(b, x, &y[..7]));
+            out.extend(popcount(b, x));
+            out
+        });
+        assert!(circuit.verify_topology());
+
+        let mut rng = thread_rng();
+        let mut cases = vec![(0, 0), (1, 1), (u64::MAX, 0), (0, u64::MAX), (1 << 63, 5)];
+        cases.extend((0..64).map(|_| (rng.gen::<u64>() >> rng.gen_range(0..64), rng.gen::<u64>() >> rng.gen_range(0..64))));
+        for (x, y) in cases {
+            let out = evaluate(&circuit, x, y);
+            let shift = (y & 127) as u32;
+            assert_eq!(bits_to_u64(&out[..64]), x.wrapping_neg());
+            assert_eq!(out[64..69], [x < y, x <= y, (x as i64) < y as i64, x as i64 <= y as i64, x == y]);
+            assert_eq!(bits_to_u64(&out[69..133]), x.min(y));
+            assert_eq!(bits_to_u64(&out[133..197]), x.max(y));
+            assert_eq!(bits_to_u64(&out[197..261]), x.checked_shl(shift).unwrap_or(0));
+            assert_eq!(bits_to_u64(&out[261..325]), x.checked_shr(shift).unwrap_or(0));
+            assert_eq!(bits_to_u64(&out[325..389]), ((x as i64) >> shift.min(63)) as u64);
+            let count = out[389..].iter().rev().fold(0, |acc, &bit| acc << 1 | bit as u32);
+            assert_eq!(count, x.count_ones());
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use crate::circuit::{Circuit, Gate};
+use crate::circuit::builder::renumber;
+
+impl Circuit {
+    /// Pushes every inversion towards the outputs, so that INV gates only remain as the
+    /// gates producing output wires.
+    ///
+    /// Inversions pass through XOR gates unchanged, and AND gates with inverted inputs are
+    /// rewritten using `!a & b = (a & b) ^ b` and `!a & !b = !((a & b) ^ a ^ b)`, which only
+    /// costs XOR gates. Copies are removed on the way.
+    pub fn absorb_inversions(&self) -> Circuit {
+        let wire_count = self.input_length + self.gates.len();
+        // Every wire is represented by a new wire and whether it is inverted.
+        let mut map = (0..wire_count).map(|wire| (wire, false)).collect::<Vec<_>>();
+        let mut gates = vec![];
+        let mut next = wire_count;
+        let mut emit = |gates: &mut Vec<(usize, Gate)>, gate| {
+            gates.push((next, gate));
+            next += 1;
+            next - 1
+        };
+
+        for &(output, gate) in self.gates.iter() {
+            map[output] = match gate {
+                Gate::Eq(c) => (emit(&mut gates, Gate::Eq(c)), false),
+                Gate::EqW(a) => map[a],
+                Gate::Inv(a) => (map[a].0, !map[a].1),
+                Gate::Xor(a, b) => {
+                    let ((a, fa), (b, fb)) = (map[a], map[b]);
+                    (emit(&mut gates, Gate::Xor(a, b)), fa ^ fb)
+                }
+                Gate::And(a, b) => {
+                    let ((a, fa), (b, fb)) = (map[a], map[b]);
+                    let and = emit(&mut gates, Gate::And(a, b));
+                    match (fa, fb) {
+                        (false, false) => (and, false),
+                        (true, false) => (emit(&mut gates, Gate::Xor(and, b)), false),
+                        (false, true) => (emit(&mut gates, Gate::Xor(and, a)), false),
+                        (true, true) => {
+                            let x = emit(&mut gates, Gate::Xor(and, a));
+                            (emit(&mut gates, Gate::Xor(x, b)), true)
+                        }
+                    }
+                }
+            };
+        }
+
+        let outputs = (wire_count - self.output_length..wire_count)
+            .map(|wire| match map[wire] {
+                (wire, false) => wire,
+                (wire, true) => emit(&mut gates, Gate::Inv(wire)),
+            })
+            .collect::<Vec<_>>();
+        let inputs = (0..self.input_length).collect::<Vec<_>>();
+        renumber(next, &inputs, self.input_groups.clone(), &gates, &outputs, self.output_groups.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, thread_rng};
+
+    use crate::circuit::{Circuit, CircuitBuilder, Gate};
+
+    #[test]
+    fn test_and() {
+        let mut builder = CircuitBuilder::new();
+        let a = builder.inputs(2);
+        let na = builder.not(a[0]);
+        let nb = builder.not(a[1]);
+        let mut outputs = vec![];
+        for &(x, y) in &[(a[0], a[1]), (na, a[1]), (a[0], nb), (na, nb)] {
+            let and = builder.and(x, y);
+            outputs.push(and);
+            outputs.push(builder.not(and));
+        }
+        builder.outputs(&outputs);
+        let circuit = builder.build();
+
+        let absorbed = circuit.absorb_inversions();
+        assert!(absorbed.verify_topology());
+        assert_eq!(absorbed.stats().and_count, 4);
+        let inverted = absorbed.gates.iter()
+            .filter(|(_, gate)| matches!(gate, Gate::Inv(_)))
+            .map(|&(output, _)| output)
+            .collect::<Vec<_>>();
+        assert_eq!(inverted.len(), 4);
+        assert!(inverted.iter().all(|&wire| wire >= absorbed.input_length + absorbed.gates.len() - 8));
+        for i in 0..4 {
+            let input = vec![i & 1 != 0, i & 2 != 0];
+            assert_eq!(absorbed.evaluate(input.clone()), circuit.evaluate(input));
+        }
+    }
+
+    #[test]
+    fn test_bundled_circuits() {
+        for text in [
+            include_str!("../../circuits/aes_128.txt"),
+            include_str!("../../circuits/sha256.txt"),
+            include_str!("../../circuits/udivide64.txt"),
+        ] {
+            let circuit: Circuit = text.parse().unwrap();
+            let absorbed = circuit.absorb_inversions();
+            assert!(absorbed.verify_topology());
+            assert_eq!(absorbed.stats().and_count, circuit.stats().and_count);
+            assert!(absorbed.stats().inv_count <= circuit.output_length);
+
+            let mut rng = thread_rng();
+            let input = (0..circuit.input_length).map(|_| rng.gen()).collect::<Vec<_>>();
+            assert_eq!(absorbed.evaluate(input.clone()), circuit.evaluate(input));
+        }
+    }
+}
@@ -0,0 +1,263 @@
+use std::ops::Range;
+
+pub use batch::{BitSlice, pack, unpack};
+pub use binary::{bristol_to_binary, CircuitDecodeError};
+pub use builder::{CircuitBuilder, Wire};
+pub(crate) use fold::Resolved;
+pub use equivalence::Equivalence;
+pub use bristol::{BristolFormat, BristolHeader, CircuitParseError, GateReader};
+pub use rewrite::RewriteReport;
+pub(crate) use slots::assign_slots;
+pub use slots::SlotAllocation;
+pub use stats::CircuitStats;
+pub use typed::{Bit, Int, TypedBuilder, Uint, Value, Width};
+pub use validation::{Diagnostic, DiagnosticKind};
+
+mod batch;
+mod binary;
+mod bristol;
+mod builder;
+mod cse;
+mod dead_gates;
+mod equivalence;
+mod fold;
+mod inversions;
+mod rewrite;
+mod sat;
+mod slots;
+pub mod gadgets;
+mod stats;
+mod typed;
+mod validation;
+
+// Bristol Fashion NOT is parsed as INV and MAND is split into one AND per output.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Gate {
+    /// Constant assignment (EQ).
+    Eq(bool),
+    /// Wire copy (EQW).
+    EqW(usize),
+    Inv(usize),
+    And(usize, usize),
+    Xor(usize, usize),
+}
+
+impl Gate {
+    /// Wires read by the gate.
+    pub fn inputs(self) -> impl Iterator<Item=usize> {
+        let (a, b) = match self {
+            Gate::Eq(_) => (None, None),
+            Gate::EqW(a) | Gate::Inv(a) => (Some(a), None),
+            Gate::And(a, b) | Gate::Xor(a, b) => (Some(a), Some(b)),
+        };
+        a.into_iter().chain(b)
+    }
+
+    /// Replaces every wire read by the gate with `f(wire)`.
+    pub fn map_inputs<F: FnMut(usize) -> usize>(self, mut f: F) -> Gate {
+        match self {
+            Gate::Eq(c) => Gate::Eq(c),
+            Gate::EqW(a) => Gate::EqW(f(a)),
+            Gate::Inv(a) => Gate::Inv(f(a)),
+            Gate::And(a, b) => Gate::And(f(a), f(b)),
+            Gate::Xor(a, b) => Gate::Xor(f(a), f(b)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Circuit {
+    pub input_length: usize,
+    pub output_length: usize,
+    /// Sizes of the input values, e.g. one per party; these sum to `input_length`.
+    pub input_groups: Vec<usize>,
+    /// Sizes of the output values; these sum to `output_length`.
+    pub output_groups: Vec<usize>,
+    pub gates: Vec<(usize, Gate)>,
+}
+
+/// Returns the range of wires covered by `group` when the wires are laid out as `groups`.
+pub fn group_range(groups: &[usize], group: usize) -> Range<usize> {
+    let start = groups[..group].iter().sum::<usize>();
+    start..start + groups[group]
+}
+
+fn split_groups<'a, T>(groups: &[usize], mut values: &'a [T]) -> Vec<&'a [T]> {
+    assert_eq!(groups.iter().sum::<usize>(), values.len());
+    groups.iter()
+        .map(|&size| {
+            let (group, rest) = values.split_at(size);
+            values = rest;
+            group
+        })
+        .collect()
+}
+
+impl Circuit {
+    pub fn input_range(&self, group: usize) -> Range<usize> {
+        group_range(&self.input_groups, group)
+    }
+
+    pub fn output_range(&self, group: usize) -> Range<usize> {
+        group_range(&self.output_groups, group)
+    }
+
+    /// Splits a full input vector into one slice per input group.
+    pub fn split_input<'a, T>(&self, input: &'a [T]) -> Vec<&'a [T]> {
+        split_groups(&self.input_groups, input)
+    }
+
+    /// Splits a full output vector into one slice per output group.
+    pub fn split_output<'a, T>(&self, output: &'a [T]) -> Vec<&'a [T]> {
+        split_groups(&self.output_groups, output)
+    }
+
+    /// Returns whether [`Circuit::validate`] finds no problems.
+    pub fn verify_topology(&self) -> bool {
+        self.validate().is_empty()
+    }
+
+    pub fn evaluate(&self, mut input: Vec<bool>) -> Vec<bool> {
+        assert_eq!(self.input_length, input.len());
+
+        let mut values = {
+            input.resize(self.input_length + self.gates.len(), false);
+            input
+        };
+
+        for &(output_wire, gate) in self.gates.iter() {
+            values[output_wire] = match gate {
+                Gate::Eq(c) => c,
+                Gate::EqW(x) => values[x],
+                Gate::Inv(x) => !values[x],
+                Gate::And(x, y) => values[x] && values[y],
+                Gate::Xor(x, y) => values[x] ^ values[y],
+            };
+        }
+
+        values.into_iter()
+            .rev()
+            .take(self.output_length)
+            .rev()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hex_literal::hex;
+
+    use crate::circuit::Circuit;
+    use crate::util::{bits_to_u64, bits_to_u8, u64_to_bits, u8_to_bits};
+
+    fn evaluate_u64(circuit: &Circuit, inputs: &[u64]) -> Vec<bool> {
+        let input = inputs.iter()
+            .cloned()
+            .flat_map(u64_to_bits)
+            .collect::<Vec<_>>();
+        circuit.evaluate(input)
+    }
+
+    #[test]
+    fn test_zero_equal() {
+        let circuit: Circuit = include_str!("../../circuits/zero_equal.txt").parse().unwrap();
+        assert!(circuit.verify_topology());
+
+        assert_eq!(evaluate_u64(&circuit, &[0]), [true]);
+        assert_eq!(evaluate_u64(&circuit, &[1000]), [false]);
+        assert_eq!(evaluate_u64(&circuit, &[5489744564]), [false]);
+        assert_eq!(evaluate_u64(&circuit, &[1]), [false]);
+    }
+
+    fn binop_u64(circuit: &Circuit, left: u64, right: u64) -> u64 {
+        bits_to_u64(&evaluate_u64(circuit, &[left, right]))
+    }
+
+    #[test]
+    fn test_adder_64() {
+        let circuit: Circuit = include_str!("../../circuits/adder64.txt").parse().unwrap();
+        assert!(circuit.verify_topology());
+
+        assert_eq!(binop_u64(&circuit, 0, 0), 0);
+        assert_eq!(binop_u64(&circuit, 1, 1), 2);
+        assert_eq!(binop_u64(&circuit, 10, 5), 15);
+        assert_eq!(binop_u64(&circuit, 0, 5000), 5000);
+        assert_eq!(binop_u64(&circuit, 300, 0), 300);
+    }
+
+    #[test]
+    fn test_groups() {
+        let circuit: Circuit = include_str!("../../circuits/adder64.txt").parse().unwrap();
+        assert_eq!(circuit.input_groups, [64, 64]);
+        assert_eq!(circuit.output_groups, [64]);
+        assert_eq!(circuit.input_range(1), 64..128);
+        assert_eq!(circuit.output_range(0), 0..64);
+
+        let input = [u64_to_bits(10), u64_to_bits(5)].concat();
+        let groups = circuit.split_input(&input);
+        assert_eq!(bits_to_u64(groups[0]), 10);
+        assert_eq!(bits_to_u64(groups[1]), 5);
+        let output = circuit.evaluate(input.clone());
+        assert_eq!(bits_to_u64(circuit.split_output(&output)[0]), 15);
+    }
+
+    #[test]
+    fn test_subtract_64() {
+        let circuit: Circuit = include_str!("../../circuits/subtract64.txt").parse().unwrap();
+        assert!(circuit.verify_topology());
+
+        assert_eq!(binop_u64(&circuit, 0, 0), 0);
+        assert_eq!(binop_u64(&circuit, 1, 1), 0);
+        assert_eq!(binop_u64(&circuit, 10, 5), 5);
+        assert_eq!(binop_u64(&circuit, 5000, 300), 4700);
+        assert_eq!(binop_u64(&circuit, 14894156165, 155644), 14894156165 - 155644);
+    }
+
+    #[test]
+    fn test_mult_64() {
+        let circuit: Circuit = include_str!("../../circuits/mult64.txt").parse().unwrap();
+        assert!(circuit.verify_topology());
+
+        assert_eq!(binop_u64(&circuit, 0, 0), 0);
+        assert_eq!(binop_u64(&circuit, 1, 1), 1);
+        assert_eq!(binop_u64(&circuit, 10, 5), 50);
+        assert_eq!(binop_u64(&circuit, 5000, 300), 5000 * 300);
+        assert_eq!(binop_u64(&circuit, 14894155, 155644), 14894155 * 155644);
+    }
+
+    #[test]
+    fn test_divide_64() {
+        let circuit: Circuit = include_str!("../../circuits/udivide64.txt").parse().unwrap();
+        assert!(circuit.verify_topology());
+
+        assert_eq!(binop_u64(&circuit, 0, 1), 0);
+        assert_eq!(binop_u64(&circuit, 1, 1), 1);
+        assert_eq!(binop_u64(&circuit, 15, 5), 3);
+        assert_eq!(binop_u64(&circuit, 5000, 200), 25);
+        assert_eq!(binop_u64(&circuit, 258290865, 165465), 1561);
+    }
+
+    #[test]
+    fn test_aes_128() {
+        let key: [u8; 16] = hex!("74 c9 f1 91 b9 02 f9 6c 32 24 3e 13 b3 5f 12 af");
+        let msg: [u8; 16] = hex!("80 14 bf b6 e6 00 f1 cd 5e ec ce c5 11 2c 4c f9");
+        let cph: [u8; 16] = hex!("7f 16 ae 5c 79 5b 18 86 b8 ca 08 b6 96 6a 7a 7c");
+
+        let mut input = key.iter().cloned()
+            .flat_map(u8_to_bits)
+            .rev()
+            .collect::<Vec<_>>();
+        input.extend(msg.iter().cloned()
+            .flat_map(u8_to_bits)
+            .rev());
+
+        let circuit: Circuit = include_str!("../../circuits/aes_128.txt").parse().unwrap();
+        assert!(circuit.verify_topology());
+        let mut output = circuit.evaluate(input);
+        output.reverse();
+        let output_bytes = output.chunks_exact(8)
+            .map(bits_to_u8)
+            .collect::<Vec<_>>();
+        assert_eq!(output_bytes, cph);
+    }
+}
@@ -0,0 +1,626 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::OnceLock;
+
+use rand::{Rng, thread_rng};
+
+use crate::circuit::{Circuit, CircuitStats, Gate};
+use crate::circuit::builder::renumber;
+
+/// Largest number of leaves of a cut.
+const CUT_SIZE: usize = 4;
+/// Cuts kept per wire during enumeration, besides the trivial one.
+const CUTS_PER_WIRE: usize = 12;
+const MAX_PASSES: usize = 8;
+/// Random inputs used to check each pass against the original circuit.
+const SIMULATION_ROUNDS: usize = 16;
+/// Every function of [`CUT_SIZE`] inputs can be computed with this many AND gates.
+const MAX_ANDS: usize = 3;
+
+/// Truth tables of the four cut leaves.
+const PROJECTIONS: [u16; CUT_SIZE] = [0xAAAA, 0xCCCC, 0xF0F0, 0xFF00];
+
+/// Outcome of [`Circuit::rewrite`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RewriteReport {
+    pub before: CircuitStats,
+    pub after: CircuitStats,
+    /// Number of passes that improved the circuit.
+    pub passes: usize,
+}
+
+impl fmt::Display for RewriteReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "AND: {} -> {}, AND depth: {} -> {}, XOR: {} -> {} in {} passes",
+            self.before.and_count, self.after.and_count,
+            self.before.and_depth, self.after.and_depth,
+            self.before.xor_count, self.after.xor_count,
+            self.passes,
+        )
+    }
+}
+
+/// Affine function of the leaves and of the outputs of earlier AND gates of a [`Recipe`],
+/// `constant ^ XOR of the values selected by mask`. Bit `i < CUT_SIZE` of the mask selects
+/// leaf `i` and bit `CUT_SIZE + j` the output of AND gate `j`.
+#[derive(Debug, Copy, Clone)]
+struct Affine {
+    mask: u8,
+    constant: bool,
+}
+
+impl Affine {
+    const ZERO: Affine = Affine { mask: 0, constant: false };
+
+    /// All affine functions of the first `vars` values.
+    fn all(vars: usize) -> impl Iterator<Item=Affine> {
+        (0..2u8 << vars).map(move |i| Affine { mask: i & ((1 << vars) - 1), constant: i >> vars != 0 })
+    }
+
+    fn truth_table(self, products: &[u16]) -> u16 {
+        PROJECTIONS.iter()
+            .chain(products)
+            .enumerate()
+            .filter(|&(i, _)| self.mask >> i & 1 != 0)
+            .fold(if self.constant { !0 } else { 0 }, |t, (_, &table)| t ^ table)
+    }
+}
+
+/// Implementation of a 4-input function with at most [`MAX_ANDS`] AND gates, each of which
+/// takes two affine functions of the leaves and the earlier AND outputs.
+#[derive(Debug, Copy, Clone)]
+struct Recipe {
+    ands: [(Affine, Affine); MAX_ANDS],
+    and_count: usize,
+    output: Affine,
+}
+
+impl Recipe {
+    fn affine(output: Affine) -> Recipe {
+        Recipe { ands: [(Affine::ZERO, Affine::ZERO); MAX_ANDS], and_count: 0, output }
+    }
+
+    fn truth_table(self) -> u16 {
+        let mut products = [0; MAX_ANDS];
+        for (i, &(a, b)) in self.ands[..self.and_count].iter().enumerate() {
+            products[i] = a.truth_table(&products[..i]) & b.truth_table(&products[..i]);
+        }
+        self.output.truth_table(&products[..self.and_count])
+    }
+
+    /// The recipe for `f(..., leaf_i ^ ..., ...)`, reading `value` wherever leaf `i` was read.
+    fn substitute(mut self, i: usize, value: Affine) -> Recipe {
+        let replace = |a: &mut Affine| if a.mask >> i & 1 != 0 {
+            a.mask ^= 1 << i ^ value.mask;
+            a.constant ^= value.constant;
+        };
+        for (a, b) in self.ands[..self.and_count].iter_mut() {
+            replace(a);
+            replace(b);
+        }
+        replace(&mut self.output);
+        self
+    }
+
+    /// Leaves the recipe reads.
+    fn mask(self) -> u8 {
+        self.ands[..self.and_count].iter()
+            .fold(self.output.mask, |mask, (a, b)| mask | a.mask | b.mask)
+            & ((1 << CUT_SIZE) - 1)
+    }
+
+    /// Largest number of AND gates on a path from a leaf to the output.
+    fn and_depth(self) -> usize {
+        let mut depths = [0; MAX_ANDS];
+        let depth = |depths: &[usize], a: Affine| (0..MAX_ANDS)
+            .filter(|j| a.mask >> (CUT_SIZE + j) & 1 != 0)
+            .map(|j| depths[j])
+            .max()
+            .unwrap_or(0);
+        for (i, &(a, b)) in self.ands[..self.and_count].iter().enumerate() {
+            depths[i] = depth(&depths, a).max(depth(&depths, b)) + 1;
+        }
+        depth(&depths, self.output)
+    }
+}
+
+/// Implementations with the fewest AND gates of every 4-input function, indexed by truth
+/// table.
+///
+/// Functions are enumerated by multiplicative complexity: affine functions, then `f ^ (a & b)`
+/// for affine `f`, `a` and `b`, then two AND gates where the second may read the first. These
+/// cover every function of degree at most three, and the functions of degree four are found
+/// with three AND gates.
+fn database() -> &'static [Option<Recipe>] {
+    static DATABASE: OnceLock<Vec<Option<Recipe>>> = OnceLock::new();
+    DATABASE.get_or_init(|| {
+        let mut database = vec![None; 1 << 16];
+        let insert = |database: &mut [Option<Recipe>], table: u16, recipe: Recipe| {
+            database[table as usize].get_or_insert(recipe);
+        };
+        for a in Affine::all(CUT_SIZE) {
+            insert(&mut database, a.truth_table(&[]), Recipe::affine(a));
+        }
+
+        // Distinct products of two affine functions of the leaves.
+        let mut seen = vec![false; 1 << 16];
+        let mut products = vec![];
+        for a in Affine::all(CUT_SIZE) {
+            for b in Affine::all(CUT_SIZE) {
+                let table = a.truth_table(&[]) & b.truth_table(&[]);
+                if !std::mem::replace(&mut seen[table as usize], true) {
+                    products.push((a, b, table));
+                }
+            }
+        }
+        for &(a, b, p) in &products {
+            for output in Affine::all(CUT_SIZE + 1) {
+                let mut recipe = Recipe::affine(output);
+                recipe.ands[0] = (a, b);
+                recipe.and_count = 1;
+                insert(&mut database, output.truth_table(&[p]), recipe);
+            }
+        }
+
+        for &(a, b, p) in &products {
+            if database[p as usize].is_some_and(|r| r.and_count == 0) {
+                continue;
+            }
+            // Independent second AND gates first, as they are shallower.
+            let mut seen = vec![false; 1 << 16];
+            for vars in [CUT_SIZE, CUT_SIZE + 1] {
+                for c in Affine::all(vars) {
+                    for d in Affine::all(vars) {
+                        let q = c.truth_table(&[p]) & d.truth_table(&[p]);
+                        if std::mem::replace(&mut seen[q as usize], true) {
+                            continue;
+                        }
+                        for output in Affine::all(CUT_SIZE + 2) {
+                            let recipe = Recipe {
+                                ands: [(a, b), (c, d), (Affine::ZERO, Affine::ZERO)],
+                                and_count: 2,
+                                output,
+                            };
+                            insert(&mut database, output.truth_table(&[p, q]), recipe);
+                        }
+                    }
+                }
+            }
+        }
+
+        // Functions of degree four need three AND gates, and any implementation with three
+        // is optimal. Products `(x1 x2 ^ e) & (x3 x4 ^ f)` reach all of them under affine
+        // changes of the leaves and adding affine functions.
+        let x1x2 = (Affine { mask: 1, constant: false }, Affine { mask: 2, constant: false });
+        let x3x4 = (Affine { mask: 4, constant: false }, Affine { mask: 8, constant: false });
+        let mut queue = VecDeque::new();
+        for e in Affine::all(CUT_SIZE) {
+            for f in Affine::all(CUT_SIZE) {
+                let g1 = Affine { mask: e.mask | 1 << CUT_SIZE, constant: e.constant };
+                let g2 = Affine { mask: f.mask | 1 << (CUT_SIZE + 1), constant: f.constant };
+                let recipe = Recipe {
+                    ands: [x1x2, x3x4, (g1, g2)],
+                    and_count: 3,
+                    output: Affine { mask: 1 << (CUT_SIZE + 2), constant: false },
+                };
+                queue.push_back(recipe);
+            }
+        }
+        while let Some(recipe) = queue.pop_front() {
+            let table = recipe.truth_table() as usize;
+            if database[table].is_some() {
+                continue;
+            }
+            database[table] = Some(recipe);
+            for i in 0..CUT_SIZE {
+                queue.push_back(recipe.substitute(i, Affine { mask: 1 << i, constant: true }));
+                queue.extend((0..CUT_SIZE)
+                    .filter(|&j| j != i)
+                    .map(|j| recipe.substitute(i, Affine { mask: 1 << i | 1 << j, constant: false })));
+                let mut added = recipe;
+                added.output.mask ^= 1 << i;
+                queue.push_back(added);
+            }
+            let mut added = recipe;
+            added.output.constant ^= true;
+            queue.push_back(added);
+        }
+        database
+    })
+}
+
+/// A chosen resynthesis of the cone between a wire and its cut.
+struct Replacement {
+    leaves: Vec<usize>,
+    recipe: Recipe,
+}
+
+impl Circuit {
+    /// Reduces the number of AND gates and, secondarily, the AND depth.
+    ///
+    /// Each pass distributes shared operands of XORed AND gates and replaces cones of up to
+    /// four inputs by an implementation with the fewest AND gates, followed by
+    /// [`Circuit::eliminate_common_subexpressions`] and [`Circuit::eliminate_dead_gates`].
+    /// Every pass is checked against the original circuit by evaluating both on random inputs;
+    /// a pass that fails the check is discarded and rewriting stops with the previous circuit.
+    pub fn rewrite(&self) -> (Circuit, RewriteReport) {
+        let before = self.stats();
+        let cost = |stats: &CircuitStats| (stats.and_count, stats.and_depth);
+        let mut best = self.clone();
+        let mut best_stats = before.clone();
+        let mut passes = 0;
+        while passes < MAX_PASSES {
+            let candidate = best.apply_identities().resynthesize_cuts();
+            let candidate = candidate.eliminate_common_subexpressions().0.eliminate_dead_gates().0;
+            let stats = candidate.stats();
+            if cost(&stats) >= cost(&best_stats) || !self.agrees_on_random_inputs(&candidate) {
+                break;
+            }
+            best = candidate;
+            best_stats = stats;
+            passes += 1;
+        }
+        (best, RewriteReport { before, after: best_stats, passes })
+    }
+
+    fn agrees_on_random_inputs(&self, other: &Circuit) -> bool {
+        let mut rng = thread_rng();
+        (0..SIMULATION_ROUNDS).all(|_| {
+            let input = (0..self.input_length).map(|_| rng.gen()).collect::<Vec<_>>();
+            self.evaluate(input.clone()) == other.evaluate(input)
+        })
+    }
+
+    /// Number of gates and outputs reading every wire.
+    fn references(&self) -> Vec<usize> {
+        let wire_count = self.input_length + self.gates.len();
+        let mut references = vec![0; wire_count];
+        for &(_, gate) in self.gates.iter() {
+            for input in gate.inputs() {
+                references[input] += 1;
+            }
+        }
+        for count in references[wire_count - self.output_length..].iter_mut() {
+            *count += 1;
+        }
+        references
+    }
+
+    /// Rewrites `(a & b) ^ (a & c)` into `a & (b ^ c)` and `a & (a & b)` into `a & b` where
+    /// the inner AND gates are not read elsewhere.
+    fn apply_identities(&self) -> Circuit {
+        let wire_count = self.input_length + self.gates.len();
+        let references = self.references();
+        let mut driver = vec![None; wire_count];
+        let mut gates = Vec::with_capacity(self.gates.len());
+        let mut next = wire_count;
+        for &(output, gate) in self.gates.iter() {
+            let exclusive_and = |wire: usize| match driver[wire] {
+                Some(Gate::And(a, b)) if references[wire] == 1 => Some((a, b)),
+                _ => None,
+            };
+            let rewritten = match gate {
+                Gate::Xor(x, y) => match (exclusive_and(x), exclusive_and(y)) {
+                    (Some((a, b)), Some((c, d))) => {
+                        let shared = if a == c || a == d { Some(a) } else if b == c || b == d { Some(b) } else { None };
+                        shared.map(|s| {
+                            let other = |(p, q): (usize, usize)| if p == s { q } else { p };
+                            gates.push((next, Gate::Xor(other((a, b)), other((c, d)))));
+                            next += 1;
+                            Gate::And(s, next - 1)
+                        })
+                    }
+                    _ => None,
+                },
+                Gate::And(x, y) => {
+                    let absorbed = |outer: usize, inner: usize| exclusive_and(inner)
+                        .filter(|&(a, b)| a == outer || b == outer)
+                        .map(|(a, b)| Gate::And(a, b));
+                    absorbed(x, y).or_else(|| absorbed(y, x))
+                }
+                _ => None,
+            };
+            let gate = rewritten.unwrap_or(gate);
+            driver[output] = Some(gate);
+            gates.push((output, gate));
+        }
+
+        let inputs = (0..self.input_length).collect::<Vec<_>>();
+        let outputs = (wire_count - self.output_length..wire_count).collect::<Vec<_>>();
+        renumber(next, &inputs, self.input_groups.clone(), &gates, &outputs, self.output_groups.clone())
+            .eliminate_dead_gates().0
+    }
+
+    /// Replaces cones of up to [`CUT_SIZE`] inputs by implementations from [`database`] where
+    /// that saves AND gates or, for the same number of AND gates, AND depth.
+    fn resynthesize_cuts(&self) -> Circuit {
+        let wire_count = self.input_length + self.gates.len();
+        let mut references = self.references();
+        let mut driver = vec![None; wire_count];
+        let mut and_depth = vec![0; wire_count];
+        let mut cuts: Vec<Vec<Vec<usize>>> = (0..wire_count).map(|wire| vec![vec![wire]]).collect();
+        // Wires that chosen replacements remove or keep alive as leaves.
+        let mut removed = vec![false; wire_count];
+        let mut kept = vec![false; wire_count];
+        let mut replacements = HashMap::new();
+
+        for &(output, gate) in self.gates.iter() {
+            driver[output] = Some(gate);
+            and_depth[output] = gate.inputs().map(|a| and_depth[a]).max().unwrap_or(0)
+                + matches!(gate, Gate::And(_, _)) as usize;
+            cuts[output] = match gate {
+                Gate::Eq(_) => vec![vec![]],
+                Gate::EqW(a) | Gate::Inv(a) => cuts[a].clone(),
+                Gate::And(a, b) | Gate::Xor(a, b) => merge_cuts(&cuts[a], &cuts[b]),
+            };
+
+            let mut best: Option<((isize, isize), Replacement, Vec<usize>)> = None;
+            for leaves in cuts[output].iter() {
+                let recipe = match database()[truth_table(&driver, output, leaves) as usize] {
+                    Some(recipe) if recipe.mask() >> leaves.len() == 0 => recipe,
+                    _ => continue,
+                };
+                let cone = free_cone(&driver, &mut references, output, leaves);
+                if cone[1..].iter().any(|&w| removed[w] || kept[w]) || leaves.iter().any(|&w| removed[w]) {
+                    continue;
+                }
+                let saved = cone.iter().filter(|&&w| matches!(driver[w], Some(Gate::And(_, _)))).count();
+                let depth = leaves.iter().map(|&w| and_depth[w]).max().unwrap_or(0) + recipe.and_depth();
+                let gain = (
+                    saved as isize - recipe.and_count as isize,
+                    and_depth[output] as isize - depth as isize,
+                );
+                if gain.0 < 0 || gain <= (0, 0) || best.as_ref().is_some_and(|(g, _, _)| gain <= *g) {
+                    continue;
+                }
+                best = Some((gain, Replacement { leaves: leaves.clone(), recipe }, cone));
+            }
+            if let Some((_, replacement, cone)) = best {
+                for &w in cone.iter().filter(|&&w| w != output) {
+                    removed[w] = true;
+                }
+                for &w in replacement.leaves.iter() {
+                    kept[w] = true;
+                }
+                and_depth[output] = replacement.leaves.iter().map(|&w| and_depth[w]).max().unwrap_or(0)
+                    + replacement.recipe.and_depth();
+                replacements.insert(output, replacement);
+            }
+            cuts[output].push(vec![output]);
+        }
+
+        let mut map = (0..wire_count).collect::<Vec<_>>();
+        let mut emitter = Emitter { gates: vec![], next: wire_count };
+        for &(output, gate) in self.gates.iter() {
+            map[output] = match (replacements.get(&output), gate) {
+                (Some(replacement), _) => {
+                    let leaves = replacement.leaves.iter().map(|&w| map[w]).collect::<Vec<_>>();
+                    emitter.recipe(&leaves, replacement.recipe)
+                }
+                (None, Gate::EqW(a)) => map[a],
+                (None, gate) => emitter.gate(gate.map_inputs(|w| map[w])),
+            };
+        }
+
+        let inputs = (0..self.input_length).collect::<Vec<_>>();
+        let outputs = (wire_count - self.output_length..wire_count)
+            .map(|wire| map[wire])
+            .collect::<Vec<_>>();
+        renumber(emitter.next, &inputs, self.input_groups.clone(), &emitter.gates, &outputs, self.output_groups.clone())
+    }
+}
+
+/// Appends gates over fresh wires starting at `next`.
+struct Emitter {
+    gates: Vec<(usize, Gate)>,
+    next: usize,
+}
+
+impl Emitter {
+    fn gate(&mut self, gate: Gate) -> usize {
+        self.gates.push((self.next, gate));
+        self.next += 1;
+        self.next - 1
+    }
+
+    fn affine(&mut self, wires: &[usize], a: Affine) -> usize {
+        let mut selected = (0..wires.len()).filter(|i| a.mask >> i & 1 != 0).map(|i| wires[i]);
+        let wire = match selected.next() {
+            Some(first) => selected.fold(first, |x, y| self.gate(Gate::Xor(x, y))),
+            None => return self.gate(Gate::Eq(a.constant)),
+        };
+        if a.constant { self.gate(Gate::Inv(wire)) } else { wire }
+    }
+
+    fn recipe(&mut self, leaves: &[usize], recipe: Recipe) -> usize {
+        // Leaves followed by the AND outputs, as indexed by `Affine::mask`.
+        let mut wires = [0; CUT_SIZE + MAX_ANDS];
+        wires[..leaves.len()].copy_from_slice(leaves);
+        for (i, &(a, b)) in recipe.ands[..recipe.and_count].iter().enumerate() {
+            let a = self.affine(&wires, a);
+            let b = self.affine(&wires, b);
+            wires[CUT_SIZE + i] = self.gate(Gate::And(a, b));
+        }
+        self.affine(&wires, recipe.output)
+    }
+}
+
+/// Unions of one cut of each operand with at most [`CUT_SIZE`] leaves, smallest first.
+fn merge_cuts(left: &[Vec<usize>], right: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    let mut merged = vec![];
+    for a in left {
+        for b in right {
+            let mut cut = a.iter().chain(b).cloned().collect::<Vec<_>>();
+            cut.sort_unstable();
+            cut.dedup();
+            if cut.len() <= CUT_SIZE && !merged.contains(&cut) {
+                merged.push(cut);
+            }
+        }
+    }
+    merged.sort_by_key(|cut| cut.len());
+    merged.truncate(CUTS_PER_WIRE);
+    merged
+}
+
+/// Truth table of `root` as a function of `leaves`.
+fn truth_table(driver: &[Option<Gate>], root: usize, leaves: &[usize]) -> u16 {
+    fn visit(driver: &[Option<Gate>], wire: usize, known: &mut HashMap<usize, u16>) -> u16 {
+        if let Some(&table) = known.get(&wire) {
+            return table;
+        }
+        let table = match driver[wire].expect("cut does not separate the wire from the inputs") {
+            Gate::Eq(c) => if c { !0 } else { 0 },
+            Gate::EqW(a) => visit(driver, a, known),
+            Gate::Inv(a) => !visit(driver, a, known),
+            Gate::And(a, b) => visit(driver, a, known) & visit(driver, b, known),
+            Gate::Xor(a, b) => visit(driver, a, known) ^ visit(driver, b, known),
+        };
+        known.insert(wire, table);
+        table
+    }
+
+    let mut known = leaves.iter().cloned().zip(PROJECTIONS.iter().cloned()).collect();
+    visit(driver, root, &mut known)
+}
+
+/// Wires, including `root`, that are only needed to compute `root` from `leaves`, which
+/// replacing the cone would remove.
+fn free_cone(driver: &[Option<Gate>], references: &mut [usize], root: usize, leaves: &[usize]) -> Vec<usize> {
+    let inner = |wire: usize| !leaves.contains(&wire) && driver[wire].is_some();
+    let mut cone = vec![root];
+    let mut i = 0;
+    while i < cone.len() {
+        for input in driver[cone[i]].into_iter().flat_map(Gate::inputs).filter(|&w| inner(w)) {
+            references[input] -= 1;
+            if references[input] == 0 {
+                cone.push(input);
+            }
+        }
+        i += 1;
+    }
+    for &wire in cone.iter() {
+        for input in driver[wire].into_iter().flat_map(Gate::inputs).filter(|&w| inner(w)) {
+            references[input] += 1;
+        }
+    }
+    cone
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::circuit::{Circuit, CircuitBuilder, gadgets};
+
+    #[test]
+    fn test_database() {
+        let database = super::database();
+        let and_count = |table: u16| database[table as usize].unwrap().and_count;
+        assert!(database.iter().all(Option::is_some));
+        // Majority has multiplicative complexity one, `ab ^ cd` and `ab | c` two, and the
+        // AND of all four leaves three.
+        assert_eq!(and_count((0xAAAA & 0xCCCC) ^ (0xAAAA & 0xF0F0) ^ (0xCCCC & 0xF0F0)), 1);
+        assert_eq!(and_count((0xAAAA & 0xCCCC) ^ (0xF0F0 & 0xFF00)), 2);
+        assert_eq!(and_count((0xAAAA & 0xCCCC) | 0xF0F0), 2);
+        assert_eq!(and_count(0xAAAA & 0xCCCC & 0xF0F0 & 0xFF00), 3);
+        // Every recipe computes its truth table.
+        for (table, recipe) in database.iter().enumerate() {
+            assert_eq!(recipe.unwrap().truth_table() as usize, table);
+        }
+    }
+
+    #[test]
+    fn test_multiplicative_complexity_two() {
+        // (a & b) ^ (c & d) ^ (a & c) = (a & (b ^ c)) ^ (c & d)
+        let mut builder = CircuitBuilder::new();
+        let a = builder.inputs(4);
+        let ab = builder.and(a[0], a[1]);
+        let cd = builder.and(a[2], a[3]);
+        let ac = builder.and(a[0], a[2]);
+        let x = builder.xor(ab, cd);
+        let y = builder.xor(x, ac);
+        builder.output(y);
+        let circuit = builder.build();
+
+        let (rewritten, report) = circuit.rewrite();
+        assert_eq!((report.before.and_count, report.after.and_count), (3, 2));
+        for i in 0..16 {
+            let input = (0..4).map(|j| i >> j & 1 != 0).collect::<Vec<_>>();
+            assert_eq!(rewritten.evaluate(input.clone()), circuit.evaluate(input));
+        }
+    }
+
+    #[test]
+    fn test_identities() {
+        let mut builder = CircuitBuilder::new();
+        let a = builder.inputs(3);
+        let x = builder.and(a[0], a[1]);
+        let y = builder.and(a[2], a[0]);
+        let z = builder.xor(x, y);
+        let w = builder.and(a[0], z);
+        builder.output(w);
+        let circuit = builder.build();
+
+        let (rewritten, report) = circuit.rewrite();
+        assert_eq!((report.before.and_count, report.after.and_count), (3, 1));
+        assert_eq!(rewritten.stats().and_count, 1);
+    }
+
+    #[test]
+    fn test_majority_and_mux() {
+        let mut builder = CircuitBuilder::new();
+        let a = builder.inputs(3);
+        let ab = builder.and(a[0], a[1]);
+        let ac = builder.and(a[0], a[2]);
+        let bc = builder.and(a[1], a[2]);
+        let x = builder.or(ab, ac);
+        let majority = builder.or(x, bc);
+        // (s & a) | (!s & b)
+        let ns = builder.not(a[0]);
+        let p = builder.and(a[0], a[1]);
+        let q = builder.and(ns, a[2]);
+        let mux = builder.or(p, q);
+        builder.outputs(&[majority, mux]);
+        let circuit = builder.build();
+
+        let (rewritten, report) = circuit.rewrite();
+        assert_eq!(report.before.and_count, 8);
+        assert_eq!(report.after.and_count, 2);
+        assert!(report.after.and_depth <= report.before.and_depth);
+        assert!(report.passes > 0);
+        for i in 0..8 {
+            let input = vec![i & 1 != 0, i & 2 != 0, i & 4 != 0];
+            assert_eq!(rewritten.evaluate(input.clone()), circuit.evaluate(input));
+        }
+    }
+
+    #[test]
+    fn test_bundled_circuits() {
+        for text in [
+            include_str!("../../circuits/adder64.txt"),
+            include_str!("../../circuits/zero_equal.txt"),
+        ] {
+            let circuit: Circuit = text.parse().unwrap();
+            let (rewritten, report) = circuit.rewrite();
+            assert!(rewritten.verify_topology());
+            assert!(report.after.and_count <= report.before.and_count);
+            assert!(circuit.agrees_on_random_inputs(&rewritten));
+        }
+    }
+
+    #[test]
+    fn test_gadgets() {
+        let mut builder = CircuitBuilder::new();
+        let x = builder.inputs(8);
+        let y = builder.inputs(8);
+        let lt = gadgets::less_than(&mut builder, &x, &y);
+        let le = gadgets::less_equal(&mut builder, &x, &y);
+        builder.outputs(&[lt, le]);
+        let circuit = builder.build();
+
+        let (rewritten, report) = circuit.rewrite();
+        assert!(report.after.and_count <= report.before.and_count);
+        assert!(circuit.agrees_on_random_inputs(&rewritten));
+    }
+}
@@ -0,0 +1,501 @@
+use std::ops::Not;
+
+/// A variable or its negation, encoded as `2 * var + negated`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct Lit(u32);
+
+impl Lit {
+    pub const fn new(var: usize, negated: bool) -> Lit {
+        Lit((var as u32) << 1 | negated as u32)
+    }
+
+    pub fn var(self) -> usize {
+        (self.0 >> 1) as usize
+    }
+
+    pub fn is_negated(self) -> bool {
+        self.0 & 1 != 0
+    }
+
+    fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl Not for Lit {
+    type Output = Lit;
+
+    fn not(self) -> Lit {
+        Lit(self.0 ^ 1)
+    }
+}
+
+/// Conflicts before the first restart, scaled by the Luby sequence.
+const RESTART_BASE: u64 = 100;
+const ACTIVITY_DECAY: f64 = 0.95;
+
+/// A conflict-driven clause learning SAT solver with two watched literals, first-UIP
+/// learning, VSIDS decisions, phase saving and Luby restarts.
+pub(crate) struct Solver {
+    clauses: Vec<Vec<Lit>>,
+    /// Clauses watching every literal, which are always the first two of the clause.
+    watches: Vec<Vec<usize>>,
+    values: Vec<Option<bool>>,
+    levels: Vec<usize>,
+    /// Clause that implied every assigned variable, whose first literal is the implied one.
+    reasons: Vec<Option<usize>>,
+    trail: Vec<Lit>,
+    /// Length of the trail when every decision was taken.
+    decisions: Vec<usize>,
+    propagated: usize,
+    conflicts: u64,
+    activity: Vec<f64>,
+    increment: f64,
+    order: Heap,
+    phases: Vec<bool>,
+    /// Whether every variable may be decided, rather than only implied.
+    decision: Vec<bool>,
+    seen: Vec<bool>,
+    /// Cleared once the clauses are known to be unsatisfiable.
+    ok: bool,
+}
+
+impl Solver {
+    pub fn new() -> Self {
+        Solver {
+            clauses: vec![],
+            watches: vec![],
+            values: vec![],
+            levels: vec![],
+            reasons: vec![],
+            trail: vec![],
+            decisions: vec![],
+            propagated: 0,
+            conflicts: 0,
+            activity: vec![],
+            increment: 1.0,
+            order: Heap::default(),
+            phases: vec![],
+            decision: vec![],
+            seen: vec![],
+            ok: true,
+        }
+    }
+
+    pub fn new_var(&mut self) -> usize {
+        let var = self.values.len();
+        self.watches.extend([vec![], vec![]]);
+        self.values.push(None);
+        self.levels.push(0);
+        self.reasons.push(None);
+        self.activity.push(0.0);
+        self.phases.push(false);
+        self.decision.push(true);
+        self.seen.push(false);
+        self.order.insert(var, &self.activity);
+        var
+    }
+
+    /// Adds a clause, discarding the model of the last call to [`Solver::solve`]. Literals
+    /// false at the top level are dropped and satisfied clauses are skipped.
+    pub fn add_clause(&mut self, lits: &[Lit]) {
+        self.backtrack(0);
+        let mut clause = lits.to_vec();
+        clause.sort_unstable();
+        clause.dedup();
+        if clause.windows(2).any(|w| w[0] == !w[1]) || clause.iter().any(|&l| self.value(l) == Some(true)) {
+            return;
+        }
+        clause.retain(|&l| self.value(l).is_none());
+        match clause.len() {
+            0 => self.ok = false,
+            1 => self.enqueue(clause[0], None),
+            _ => {
+                self.attach(clause);
+            }
+        }
+    }
+
+    /// Returns whether the clauses are satisfiable, in which case [`Solver::model`] holds an
+    /// assignment satisfying them.
+    pub fn solve(&mut self) -> bool {
+        self.solve_limited(&[], None).unwrap()
+    }
+
+    /// Like [`Solver::solve`], with every literal of `assumptions` forced to hold for this call
+    /// only. Gives up with `None` once `conflict_budget` conflicts occurred.
+    pub fn solve_limited(&mut self, assumptions: &[Lit], conflict_budget: Option<u64>) -> Option<bool> {
+        self.backtrack(0);
+        if !self.ok || self.propagate().is_some() {
+            self.ok = false;
+            return Some(false);
+        }
+        let start = self.conflicts;
+        for restart in 0.. {
+            let mut limit = luby(restart) * RESTART_BASE;
+            if let Some(budget) = conflict_budget {
+                let used = self.conflicts - start;
+                if used >= budget {
+                    self.backtrack(0);
+                    return None;
+                }
+                limit = limit.min(budget - used);
+            }
+            if let Some(result) = self.search(assumptions, limit) {
+                return Some(result);
+            }
+            self.backtrack(0);
+        }
+        unreachable!()
+    }
+
+    /// Excludes `var` from decisions, which keeps the solver complete as long as every
+    /// assignment of the remaining variables implies it, such as internal wires of a circuit.
+    pub fn set_decision(&mut self, var: usize, decision: bool) {
+        self.decision[var] = decision;
+        if decision {
+            self.order.insert(var, &self.activity);
+        }
+    }
+
+    pub fn model(&self, var: usize) -> bool {
+        self.values[var] == Some(true)
+    }
+
+    fn value(&self, lit: Lit) -> Option<bool> {
+        self.values[lit.var()].map(|v| v ^ lit.is_negated())
+    }
+
+    fn attach(&mut self, clause: Vec<Lit>) -> usize {
+        let index = self.clauses.len();
+        self.watches[clause[0].index()].push(index);
+        self.watches[clause[1].index()].push(index);
+        self.clauses.push(clause);
+        index
+    }
+
+    fn enqueue(&mut self, lit: Lit, reason: Option<usize>) {
+        let var = lit.var();
+        self.values[var] = Some(!lit.is_negated());
+        self.levels[var] = self.decisions.len();
+        self.reasons[var] = reason;
+        self.trail.push(lit);
+    }
+
+    /// Propagates every unit clause, returning a clause that became false.
+    fn propagate(&mut self) -> Option<usize> {
+        while self.propagated < self.trail.len() {
+            let false_lit = !self.trail[self.propagated];
+            self.propagated += 1;
+            let mut watchers = std::mem::take(&mut self.watches[false_lit.index()]);
+            let mut conflict = None;
+            let mut i = 0;
+            while i < watchers.len() {
+                let index = watchers[i];
+                let clause = &mut self.clauses[index];
+                if clause[0] == false_lit {
+                    clause.swap(0, 1);
+                }
+                let first = clause[0];
+                if self.values[first.var()].map(|v| v ^ first.is_negated()) == Some(true) {
+                    i += 1;
+                    continue;
+                }
+                let values = &self.values;
+                let replacement = (2..clause.len())
+                    .find(|&k| values[clause[k].var()].map(|v| v ^ clause[k].is_negated()) != Some(false));
+                if let Some(k) = replacement {
+                    clause.swap(1, k);
+                    self.watches[clause[1].index()].push(index);
+                    watchers.swap_remove(i);
+                    continue;
+                }
+                i += 1;
+                if self.value(first) == Some(false) {
+                    conflict = Some(index);
+                    break;
+                }
+                self.enqueue(first, Some(index));
+            }
+            self.watches[false_lit.index()] = watchers;
+            if conflict.is_some() {
+                return conflict;
+            }
+        }
+        None
+    }
+
+    /// Learns the first-UIP clause of a conflict, with the asserting literal first and a
+    /// literal of the backtrack level second, and returns it with the backtrack level.
+    fn analyze(&mut self, conflict: usize) -> (Vec<Lit>, usize) {
+        let level = self.decisions.len();
+        let mut learnt = vec![Lit(0)];
+        let mut pending = 0;
+        let mut clause = conflict;
+        let mut skip = 0;
+        let mut index = self.trail.len();
+        loop {
+            for k in skip..self.clauses[clause].len() {
+                let lit = self.clauses[clause][k];
+                let var = lit.var();
+                if !self.seen[var] && self.levels[var] > 0 {
+                    self.seen[var] = true;
+                    self.bump(var);
+                    if self.levels[var] == level {
+                        pending += 1;
+                    } else {
+                        learnt.push(lit);
+                    }
+                }
+            }
+            loop {
+                index -= 1;
+                if self.seen[self.trail[index].var()] {
+                    break;
+                }
+            }
+            let lit = self.trail[index];
+            self.seen[lit.var()] = false;
+            pending -= 1;
+            if pending == 0 {
+                learnt[0] = !lit;
+                break;
+            }
+            clause = self.reasons[lit.var()].unwrap();
+            skip = 1;
+        }
+        for lit in &learnt[1..] {
+            self.seen[lit.var()] = false;
+        }
+
+        let mut backtrack = 0;
+        if learnt.len() > 1 {
+            let highest = (1..learnt.len()).max_by_key(|&k| self.levels[learnt[k].var()]).unwrap();
+            learnt.swap(1, highest);
+            backtrack = self.levels[learnt[1].var()];
+        }
+        (learnt, backtrack)
+    }
+
+    fn backtrack(&mut self, level: usize) {
+        if self.decisions.len() <= level {
+            return;
+        }
+        for &lit in &self.trail[self.decisions[level]..] {
+            let var = lit.var();
+            self.phases[var] = !lit.is_negated();
+            self.values[var] = None;
+            self.reasons[var] = None;
+            if self.decision[var] {
+                self.order.insert(var, &self.activity);
+            }
+        }
+        self.trail.truncate(self.decisions[level]);
+        self.decisions.truncate(level);
+        self.propagated = self.trail.len();
+    }
+
+    /// Searches until a result is found or `conflict_limit` conflicts occurred.
+    fn search(&mut self, assumptions: &[Lit], conflict_limit: u64) -> Option<bool> {
+        let mut conflicts = 0;
+        loop {
+            if let Some(conflict) = self.propagate() {
+                if self.decisions.is_empty() {
+                    self.ok = false;
+                    return Some(false);
+                }
+                conflicts += 1;
+                self.conflicts += 1;
+                let (learnt, level) = self.analyze(conflict);
+                self.backtrack(level);
+                let asserting = learnt[0];
+                let reason = if learnt.len() > 1 { Some(self.attach(learnt)) } else { None };
+                self.enqueue(asserting, reason);
+                self.increment /= ACTIVITY_DECAY;
+            } else {
+                if conflicts >= conflict_limit {
+                    return None;
+                }
+                // Assumptions are decided first, one per level.
+                let lit = match assumptions.get(self.decisions.len()) {
+                    Some(&lit) => match self.value(lit) {
+                        Some(true) => {
+                            self.decisions.push(self.trail.len());
+                            continue;
+                        }
+                        Some(false) => return Some(false),
+                        None => lit,
+                    },
+                    None => loop {
+                        match self.order.pop(&self.activity) {
+                            None => return Some(true),
+                            Some(var) if self.values[var].is_none() && self.decision[var] => break Lit::new(var, !self.phases[var]),
+                            Some(_) => {}
+                        }
+                    },
+                };
+                self.decisions.push(self.trail.len());
+                self.enqueue(lit, None);
+            }
+        }
+    }
+
+    fn bump(&mut self, var: usize) {
+        self.activity[var] += self.increment;
+        if self.activity[var] > 1e100 {
+            self.activity.iter_mut().for_each(|a| *a *= 1e-100);
+            self.increment *= 1e-100;
+        }
+        self.order.increased(var, &self.activity);
+    }
+}
+
+/// Element `i` of the Luby sequence 1, 1, 2, 1, 1, 2, 4, ...
+fn luby(mut i: u64) -> u64 {
+    let mut size = 1;
+    let mut exponent = 0;
+    while size < i + 1 {
+        exponent += 1;
+        size = 2 * size + 1;
+    }
+    while size - 1 != i {
+        size = (size - 1) >> 1;
+        exponent -= 1;
+        i %= size;
+    }
+    1 << exponent
+}
+
+/// Binary max-heap of variables by activity.
+#[derive(Default)]
+struct Heap {
+    heap: Vec<usize>,
+    positions: Vec<Option<usize>>,
+}
+
+impl Heap {
+    fn insert(&mut self, var: usize, activity: &[f64]) {
+        if self.positions.len() <= var {
+            self.positions.resize(var + 1, None);
+        }
+        if self.positions[var].is_none() {
+            self.positions[var] = Some(self.heap.len());
+            self.heap.push(var);
+            self.sift_up(self.heap.len() - 1, activity);
+        }
+    }
+
+    fn increased(&mut self, var: usize, activity: &[f64]) {
+        if let Some(position) = self.positions[var] {
+            self.sift_up(position, activity);
+        }
+    }
+
+    fn pop(&mut self, activity: &[f64]) -> Option<usize> {
+        let top = *self.heap.first()?;
+        let last = self.heap.pop().unwrap();
+        self.positions[top] = None;
+        if !self.heap.is_empty() {
+            self.heap[0] = last;
+            self.positions[last] = Some(0);
+            self.sift_down(0, activity);
+        }
+        Some(top)
+    }
+
+    fn sift_up(&mut self, mut i: usize, activity: &[f64]) {
+        while i > 0 {
+            let parent = (i - 1) / 2;
+            if activity[self.heap[parent]] >= activity[self.heap[i]] {
+                break;
+            }
+            self.swap(i, parent);
+            i = parent;
+        }
+    }
+
+    fn sift_down(&mut self, mut i: usize, activity: &[f64]) {
+        loop {
+            let mut largest = i;
+            for child in [2 * i + 1, 2 * i + 2] {
+                if child < self.heap.len() && activity[self.heap[child]] > activity[self.heap[largest]] {
+                    largest = child;
+                }
+            }
+            if largest == i {
+                break;
+            }
+            self.swap(i, largest);
+            i = largest;
+        }
+    }
+
+    fn swap(&mut self, i: usize, j: usize) {
+        self.heap.swap(i, j);
+        self.positions[self.heap[i]] = Some(i);
+        self.positions[self.heap[j]] = Some(j);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, thread_rng};
+
+    use super::*;
+
+    #[test]
+    fn test_luby() {
+        let sequence = (0..15).map(luby).collect::<Vec<_>>();
+        assert_eq!(sequence, [1, 1, 2, 1, 1, 2, 4, 1, 1, 2, 1, 1, 2, 4, 8]);
+    }
+
+    #[test]
+    fn test_pigeonhole() {
+        // Six pigeons do not fit in five holes.
+        let (pigeons, holes) = (6, 5);
+        let mut solver = Solver::new();
+        let vars = (0..pigeons * holes).map(|_| solver.new_var()).collect::<Vec<_>>();
+        for p in 0..pigeons {
+            let clause = (0..holes).map(|h| Lit::new(vars[p * holes + h], false)).collect::<Vec<_>>();
+            solver.add_clause(&clause);
+        }
+        for h in 0..holes {
+            for p in 0..pigeons {
+                for q in p + 1..pigeons {
+                    solver.add_clause(&[Lit::new(vars[p * holes + h], true), Lit::new(vars[q * holes + h], true)]);
+                }
+            }
+        }
+        assert!(!solver.solve());
+    }
+
+    #[test]
+    fn test_random_3sat() {
+        let mut rng = thread_rng();
+        let vars = 12;
+        for _ in 0..200 {
+            let clauses = (0..rng.gen_range(30..70))
+                .map(|_| (0..3).map(|_| Lit::new(rng.gen_range(0..vars), rng.gen())).collect::<Vec<_>>())
+                .collect::<Vec<_>>();
+            let satisfies = |assignment: &dyn Fn(usize) -> bool| clauses.iter()
+                .all(|clause| clause.iter().any(|&l| assignment(l.var()) ^ l.is_negated()));
+            let expected = (0..1u32 << vars).any(|bits| satisfies(&|var| bits >> var & 1 != 0));
+
+            let mut solver = Solver::new();
+            (0..vars).for_each(|_| { solver.new_var(); });
+            clauses.iter().for_each(|clause| solver.add_clause(clause));
+            assert_eq!(solver.solve(), expected);
+            if expected {
+                assert!(satisfies(&|var| solver.model(var)));
+            }
+
+            // Assumptions only hold for a single call.
+            let assumption = Lit::new(rng.gen_range(0..vars), rng.gen());
+            let expected_assuming = (0..1u32 << vars)
+                .any(|bits| bits >> assumption.var() & 1 != assumption.is_negated() as u32
+                    && satisfies(&|var| bits >> var & 1 != 0));
+            assert_eq!(solver.solve_limited(&[assumption], None), Some(expected_assuming));
+            assert_eq!(solver.solve(), expected);
+        }
+    }
+}
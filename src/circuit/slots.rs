@@ -0,0 +1,147 @@
+use crate::circuit::{Circuit, Gate};
+
+/// A circuit whose gates read and write a pool of reusable value slots instead of one slot
+/// per wire, so evaluation needs memory proportional to the peak number of live wires.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlotAllocation {
+    pub input_length: usize,
+    /// Number of slots; inputs occupy slots `0..input_length` at the start.
+    pub slot_count: usize,
+    /// Gates over slots, in the order of the circuit.
+    pub gates: Vec<(usize, Gate)>,
+    /// Slot holding every output after the last gate.
+    pub outputs: Vec<usize>,
+}
+
+/// Assigns every wire a slot, reusing the slots of wires after their last read.
+///
+/// Inputs keep slots `0..input_length` and `outputs` are never released. A gate may write
+/// the slot of one of its inputs, so evaluators must read all inputs before writing.
+/// Returns the slot of every wire along with the number of slots.
+pub(crate) fn assign_slots<T, F, I>(
+    input_length: usize,
+    wire_count: usize,
+    gates: &[(usize, T)],
+    outputs: &[usize],
+    inputs: F,
+) -> (Vec<usize>, usize)
+    where F: Fn(&T) -> I, I: Iterator<Item=usize> {
+    let mut last_use = vec![None; wire_count];
+    for (i, (_, gate)) in gates.iter().enumerate() {
+        for wire in inputs(gate) {
+            last_use[wire] = Some(i);
+        }
+    }
+    for &wire in outputs {
+        last_use[wire] = Some(usize::MAX);
+    }
+
+    let mut slots = vec![usize::MAX; wire_count];
+    let mut free = vec![];
+    for (wire, slot) in slots.iter_mut().enumerate().take(input_length) {
+        *slot = wire;
+        if last_use[wire].is_none() {
+            free.push(wire);
+        }
+    }
+    let mut slot_count = input_length;
+    for (i, (output, gate)) in gates.iter().enumerate() {
+        for wire in inputs(gate) {
+            if last_use[wire] == Some(i) {
+                // Clearing the last use also keeps a wire read twice from being freed twice.
+                last_use[wire] = None;
+                free.push(slots[wire]);
+            }
+        }
+        let slot = free.pop().unwrap_or_else(|| {
+            slot_count += 1;
+            slot_count - 1
+        });
+        slots[*output] = slot;
+        if last_use[*output].is_none() {
+            free.push(slot);
+        }
+    }
+    (slots, slot_count)
+}
+
+impl Circuit {
+    pub fn allocate_slots(&self) -> SlotAllocation {
+        let wire_count = self.input_length + self.gates.len();
+        let outputs = (wire_count - self.output_length..wire_count).collect::<Vec<_>>();
+        let (slots, slot_count) = assign_slots(self.input_length, wire_count, &self.gates, &outputs, |gate| gate.inputs());
+        SlotAllocation {
+            input_length: self.input_length,
+            slot_count,
+            gates: self.gates.iter()
+                .map(|&(output, gate)| (slots[output], gate.map_inputs(|wire| slots[wire])))
+                .collect(),
+            outputs: outputs.iter().map(|&wire| slots[wire]).collect(),
+        }
+    }
+}
+
+impl SlotAllocation {
+    /// Same as [`Circuit::evaluate`], using `slot_count` values.
+    pub fn evaluate(&self, mut input: Vec<bool>) -> Vec<bool> {
+        assert_eq!(self.input_length, input.len());
+
+        let mut values = {
+            input.resize(self.slot_count, false);
+            input
+        };
+
+        for &(output_slot, gate) in self.gates.iter() {
+            values[output_slot] = match gate {
+                Gate::Eq(c) => c,
+                Gate::EqW(x) => values[x],
+                Gate::Inv(x) => !values[x],
+                Gate::And(x, y) => values[x] && values[y],
+                Gate::Xor(x, y) => values[x] ^ values[y],
+            };
+        }
+
+        self.outputs.iter()
+            .map(|&slot| values[slot])
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, thread_rng};
+
+    use crate::circuit::{Circuit, Gate};
+
+    #[test]
+    fn test_reuse() {
+        // out = (a & b) ^ (a ^ b), out = !a
+        let circuit: Circuit = "4 6\n1 2\n1 2\n\n2 1 0 1 2 AND\n2 1 0 1 3 XOR\n2 1 2 3 4 XOR\n1 1 0 5 INV\n"
+            .parse().unwrap();
+        let slots = circuit.allocate_slots();
+        // Slots of inputs are reused once they are last read, even by the reading gate.
+        assert_eq!(slots.slot_count, 3);
+        assert_eq!(slots.gates, [(2, Gate::And(0, 1)), (1, Gate::Xor(0, 1)), (1, Gate::Xor(2, 1)), (0, Gate::Inv(0))]);
+        assert_eq!(slots.outputs, [1, 0]);
+        for i in 0..4 {
+            let input = vec![i & 1 != 0, i & 2 != 0];
+            assert_eq!(slots.evaluate(input.clone()), circuit.evaluate(input));
+        }
+    }
+
+    #[test]
+    fn test_bundled_circuits() {
+        for text in [
+            include_str!("../../circuits/aes_128.txt"),
+            include_str!("../../circuits/sha256.txt"),
+        ] {
+            let circuit: Circuit = text.parse().unwrap();
+            let slots = circuit.allocate_slots();
+            assert!(slots.slot_count <= circuit.stats().peak_live_wires + 1);
+            assert!(slots.slot_count * 10 < circuit.input_length + circuit.gates.len());
+
+            let input = (0..circuit.input_length).map(|_| thread_rng().gen()).collect::<Vec<_>>();
+            assert_eq!(slots.evaluate(input.clone()), circuit.evaluate(input));
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use std::fmt;
+
+use crate::circuit::{Circuit, Gate};
+use crate::garbled_circuit::classic::Classic;
+use crate::garbled_circuit::flexor::FleXor;
+use crate::garbled_circuit::half_gates::HalfGates;
+
+/// Summary numbers used to compare circuits, along with the garbled size of the classic and
+/// the half gates scheme. Other schemes are estimated by [`Circuit::estimated_size`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct CircuitStats {
+    pub and_count: usize,
+    pub xor_count: usize,
+    pub inv_count: usize,
+    /// Number of EQ (constant) gates.
+    pub constant_count: usize,
+    /// Number of EQW (copy) gates.
+    pub copy_count: usize,
+    /// Longest path measured in AND, XOR and INV gates.
+    pub depth: usize,
+    /// Largest number of AND gates on any path.
+    pub and_depth: usize,
+    pub max_fan_out: usize,
+    /// Gate inputs per wire.
+    pub average_fan_out: f64,
+    /// Largest number of wires that are simultaneously written and still needed later.
+    pub peak_live_wires: usize,
+    /// Estimated garbled size of [`Classic`] in bytes.
+    pub classic_bytes: usize,
+    /// Estimated garbled size of [`HalfGates`] in bytes.
+    pub half_gates_bytes: usize,
+    /// Number of XOR gates [`FleXor`] garbles with 0, 1 and 2 ciphertexts.
+    pub flexor_xor_ciphertexts: [usize; 3],
+}
+
+impl Circuit {
+    /// Computes the [`CircuitStats`] of the circuit in a single pass over the gates.
+    ///
+    /// # Panics
+    ///
+    /// If [`Circuit::validate`] reports problems with the circuit.
+    pub fn stats(&self) -> CircuitStats {
+        let wire_count = self.input_length + self.gates.len();
+        let mut depth = vec![0; wire_count];
+        let mut and_depth = vec![0; wire_count];
+        let mut fan_out = vec![0; wire_count];
+        let mut last_use = vec![None; wire_count];
+        let (mut and_count, mut xor_count, mut inv_count, mut constant_count, mut copy_count) = (0, 0, 0, 0, 0);
+
+        for (i, &(output, gate)) in self.gates.iter().enumerate() {
+            let (d, ad) = match gate {
+                Gate::Eq(_) => {
+                    constant_count += 1;
+                    (0, 0)
+                }
+                Gate::EqW(a) => {
+                    copy_count += 1;
+                    (depth[a], and_depth[a])
+                }
+                Gate::Inv(a) => {
+                    inv_count += 1;
+                    (depth[a] + 1, and_depth[a])
+                }
+                Gate::Xor(a, b) => {
+                    xor_count += 1;
+                    (depth[a].max(depth[b]) + 1, and_depth[a].max(and_depth[b]))
+                }
+                Gate::And(a, b) => {
+                    and_count += 1;
+                    (depth[a].max(depth[b]) + 1, and_depth[a].max(and_depth[b]) + 1)
+                }
+            };
+            depth[output] = d;
+            and_depth[output] = ad;
+            for input in gate.inputs() {
+                fan_out[input] += 1;
+                last_use[input] = Some(i);
+            }
+        }
+
+        // Inputs are live from the start and outputs until the end.
+        let outputs = wire_count - self.output_length..wire_count;
+        let mut live = self.input_length;
+        let mut peak_live_wires = live;
+        let mut dying = vec![0; self.gates.len()];
+        for (wire, &last) in last_use.iter().enumerate() {
+            match last {
+                _ if outputs.contains(&wire) => {}
+                Some(last) => dying[last] += 1,
+                // Inputs that are never read die immediately.
+                None if wire < self.input_length => live -= 1,
+                None => {}
+            }
+        }
+        for (i, &(output, _)) in self.gates.iter().enumerate() {
+            if last_use[output].is_some() || outputs.contains(&output) {
+                live += 1;
+            }
+            peak_live_wires = peak_live_wires.max(live);
+            live -= dying[i];
+        }
+
+        CircuitStats {
+            and_count,
+            xor_count,
+            inv_count,
+            constant_count,
+            copy_count,
+            depth: depth.iter().cloned().max().unwrap_or(0),
+            and_depth: and_depth.iter().cloned().max().unwrap_or(0),
+            max_fan_out: fan_out.iter().cloned().max().unwrap_or(0),
+            average_fan_out: fan_out.iter().sum::<usize>() as f64 / wire_count.max(1) as f64,
+            peak_live_wires,
+            classic_bytes: self.estimated_size::<Classic, _, _>(),
+            half_gates_bytes: self.estimated_size::<HalfGates, _, _>(),
+            flexor_xor_ciphertexts: FleXor::xor_ciphertexts(self),
+        }
+    }
+}
+
+impl fmt::Display for CircuitStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "AND: {}, XOR: {}, INV: {}, EQ: {}, EQW: {}, depth: {}, AND depth: {}, \
+             fan-out: {} max / {:.2} avg, peak live wires: {}, Classic: {} B, HalfGates: {} B, \
+             FleXOR XOR gates with 0/1/2 ciphertexts: {}/{}/{}",
+            self.and_count, self.xor_count, self.inv_count, self.constant_count, self.copy_count,
+            self.depth, self.and_depth, self.max_fan_out, self.average_fan_out,
+            self.peak_live_wires, self.classic_bytes, self.half_gates_bytes,
+            self.flexor_xor_ciphertexts[0], self.flexor_xor_ciphertexts[1], self.flexor_xor_ciphertexts[2],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::circuit::Circuit;
+
+    #[test]
+    fn test_small() {
+        // out0 = (a & b) ^ !c, out1 = a & b
+        let circuit: Circuit = "4 7\n1 3\n1 2\n\n2 1 0 1 3 AND\n1 1 2 4 INV\n2 1 3 4 5 XOR\n1 1 3 6 EQW\n"
+            .parse().unwrap();
+        let stats = circuit.stats();
+        assert_eq!((stats.and_count, stats.xor_count, stats.inv_count, stats.copy_count), (1, 1, 1, 1));
+        assert_eq!((stats.depth, stats.and_depth), (2, 1));
+        assert_eq!(stats.max_fan_out, 2);
+        assert_eq!(stats.average_fan_out, 6.0 / 7.0);
+        assert_eq!(stats.peak_live_wires, 4);
+        assert_eq!(stats.classic_bytes, 8 * 32);
+        assert_eq!(stats.half_gates_bytes, 32);
+        // The AND output is unaligned and !c is below the level of the XOR output.
+        assert_eq!(stats.flexor_xor_ciphertexts, [0, 0, 1]);
+    }
+
+    #[test]
+    fn test_aes_128() {
+        let circuit: Circuit = include_str!("../../circuits/aes_128.txt").parse().unwrap();
+        let stats = circuit.stats();
+        assert_eq!((stats.and_count, stats.xor_count, stats.inv_count), (6400, 28176, 2087));
+        assert!(stats.and_depth <= stats.depth);
+        assert!(stats.peak_live_wires <= circuit.input_length + circuit.gates.len());
+        assert_eq!(stats.half_gates_bytes, 32 * stats.and_count);
+    }
+}
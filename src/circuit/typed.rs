@@ -0,0 +1,599 @@
+use std::cell::RefCell;
+use std::convert::TryInto;
+use std::ops::{Add, BitAnd, BitOr, BitXor, Div, Mul, Neg, Not, Rem, Shl, Shr, Sub};
+use std::rc::Rc;
+
+use crate::circuit::{Circuit, CircuitBuilder, gadgets, Wire};
+
+/// Shared [`CircuitBuilder`] that [`Bit`], [`Uint`] and [`Int`] values record their gates into.
+///
+/// Values keep a handle to the builder, so operators can be used without passing it around.
+#[derive(Debug, Clone, Default)]
+pub struct TypedBuilder(Rc<RefCell<CircuitBuilder>>);
+
+/// A typed bundle of wires.
+pub trait Value: Sized {
+    fn wires(&self) -> Vec<Wire>;
+
+    fn from_wires(builder: &TypedBuilder, wires: &[Wire]) -> Self;
+}
+
+impl TypedBuilder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    fn with<T, F: FnOnce(&mut CircuitBuilder) -> T>(&self, f: F) -> T {
+        f(&mut self.0.borrow_mut())
+    }
+
+    fn word<const N: usize>(&self, wires: Vec<Wire>) -> [Wire; N] {
+        wires.try_into().expect("word has the wrong width")
+    }
+
+    /// Adds a new input group holding a value of type `T`.
+    pub fn input<T: Value + Width>(&self) -> T {
+        let wires = self.with(|b| b.inputs(T::WIDTH));
+        T::from_wires(self, &wires)
+    }
+
+    /// Marks `value` as a new output group.
+    pub fn output<T: Value>(&self, value: &T) {
+        self.with(|b| b.outputs(&value.wires()));
+    }
+
+    pub fn bit(&self, value: bool) -> Bit {
+        Bit { builder: self.clone(), wire: self.with(|b| b.constant(value)) }
+    }
+
+    /// A constant holding the low `N` bits of `value`.
+    pub fn uint<const N: usize>(&self, value: u128) -> Uint<N> {
+        let wires = (0..N)
+            .map(|i| self.with(|b| b.constant(i < 128 && (value >> i) & 1 != 0)))
+            .collect();
+        Uint { builder: self.clone(), wires: self.word(wires) }
+    }
+
+    /// A constant holding the low `N` bits of `value`, sign extended.
+    pub fn int<const N: usize>(&self, value: i128) -> Int<N> {
+        let wires = (0..N)
+            .map(|i| self.with(|b| b.constant((value >> i.min(127)) & 1 != 0)))
+            .collect();
+        Int { builder: self.clone(), wires: self.word(wires) }
+    }
+
+    /// Takes the gates recorded so far and builds them into a circuit.
+    pub fn build(&self) -> Circuit {
+        self.0.take().build()
+    }
+}
+
+/// Number of wires in a value of fixed type.
+pub trait Width {
+    const WIDTH: usize;
+}
+
+/// A single wire.
+#[derive(Debug, Clone)]
+pub struct Bit {
+    builder: TypedBuilder,
+    wire: Wire,
+}
+
+/// An unsigned `N`-bit integer with wrapping arithmetic.
+#[derive(Debug, Clone)]
+pub struct Uint<const N: usize> {
+    builder: TypedBuilder,
+    wires: [Wire; N],
+}
+
+/// A two's complement `N`-bit integer with wrapping arithmetic.
+#[derive(Debug, Clone)]
+pub struct Int<const N: usize> {
+    builder: TypedBuilder,
+    wires: [Wire; N],
+}
+
+impl Value for Bit {
+    fn wires(&self) -> Vec<Wire> {
+        vec![self.wire]
+    }
+
+    fn from_wires(builder: &TypedBuilder, wires: &[Wire]) -> Self {
+        assert_eq!(wires.len(), 1);
+        Bit { builder: builder.clone(), wire: wires[0] }
+    }
+}
+
+impl Width for Bit {
+    const WIDTH: usize = 1;
+}
+
+impl Bit {
+    pub fn wire(&self) -> Wire {
+        self.wire
+    }
+
+    fn gate<F: FnOnce(&mut CircuitBuilder) -> Wire>(&self, f: F) -> Bit {
+        Bit { builder: self.builder.clone(), wire: self.builder.with(f) }
+    }
+
+    /// Returns `a` if the bit is set and `b` otherwise.
+    pub fn select<T: Value>(&self, a: &T, b: &T) -> T {
+        let wires = self.builder.with(|builder| gadgets::mux(builder, self.wire, &b.wires(), &a.wires()));
+        T::from_wires(&self.builder, &wires)
+    }
+}
+
+impl BitAnd for &Bit {
+    type Output = Bit;
+
+    fn bitand(self, rhs: Self) -> Bit {
+        self.gate(|b| b.and(self.wire, rhs.wire))
+    }
+}
+
+impl BitXor for &Bit {
+    type Output = Bit;
+
+    fn bitxor(self, rhs: Self) -> Bit {
+        self.gate(|b| b.xor(self.wire, rhs.wire))
+    }
+}
+
+impl BitOr for &Bit {
+    type Output = Bit;
+
+    fn bitor(self, rhs: Self) -> Bit {
+        self.gate(|b| b.or(self.wire, rhs.wire))
+    }
+}
+
+impl Not for &Bit {
+    type Output = Bit;
+
+    fn not(self) -> Bit {
+        self.gate(|b| b.not(self.wire))
+    }
+}
+
+/// Implements an operator for owned values in terms of the implementation for references.
+macro_rules! forward_owned {
+    (impl$(<$n:ident>)? $trait:ident::$method:ident for $t:ty) => {
+        impl$(<const $n: usize>)? $trait for $t {
+            type Output = $t;
+
+            fn $method(self, rhs: Self) -> $t {
+                (&self).$method(&rhs)
+            }
+        }
+
+        impl$(<const $n: usize>)? $trait<&$t> for $t {
+            type Output = $t;
+
+            fn $method(self, rhs: &$t) -> $t {
+                (&self).$method(rhs)
+            }
+        }
+
+        impl$(<const $n: usize>)? $trait<$t> for &$t {
+            type Output = $t;
+
+            fn $method(self, rhs: $t) -> $t {
+                self.$method(&rhs)
+            }
+        }
+    };
+    (impl$(<$n:ident>)? $trait:ident::$method:ident for $t:ty, unary) => {
+        impl$(<const $n: usize>)? $trait for $t {
+            type Output = $t;
+
+            fn $method(self) -> $t {
+                (&self).$method()
+            }
+        }
+    };
+}
+
+forward_owned!(impl BitAnd::bitand for Bit);
+forward_owned!(impl BitXor::bitxor for Bit);
+forward_owned!(impl BitOr::bitor for Bit);
+forward_owned!(impl Not::not for Bit, unary);
+
+fn bitwise<F>(b: &mut CircuitBuilder, x: &[Wire], y: &[Wire], mut f: F) -> Vec<Wire>
+    where F: FnMut(&mut CircuitBuilder, Wire, Wire) -> Wire {
+    x.iter().zip(y).map(|(&a, &c)| f(b, a, c)).collect()
+}
+
+/// Shifts by a constant amount, which only rewires apart from the constant filling. The
+/// constant is only added if some bit is shifted out.
+fn shift_constant(b: &mut CircuitBuilder, x: &[Wire], amount: usize, left: bool, mut fill: Option<Wire>) -> Vec<Wire> {
+    let n = x.len();
+    (0..n)
+        .map(|i| {
+            let source = if left { i.checked_sub(amount) } else { i.checked_add(amount).filter(|&j| j < n) };
+            source.map_or_else(|| *fill.get_or_insert_with(|| b.constant(false)), |j| x[j])
+        })
+        .collect()
+}
+
+/// Implements the operators and comparisons shared by [`Uint`] and [`Int`].
+macro_rules! impl_word {
+    ($t:ident, $lt:path, $shr:expr, $shr_secret:path) => {
+        impl<const N: usize> Value for $t<N> {
+            fn wires(&self) -> Vec<Wire> {
+                self.wires.to_vec()
+            }
+
+            fn from_wires(builder: &TypedBuilder, wires: &[Wire]) -> Self {
+                $t { builder: builder.clone(), wires: builder.word(wires.to_vec()) }
+            }
+        }
+
+        impl<const N: usize> Width for $t<N> {
+            const WIDTH: usize = N;
+        }
+
+        impl<const N: usize> $t<N> {
+            fn word<F: FnOnce(&mut CircuitBuilder) -> Vec<Wire>>(&self, f: F) -> $t<N> {
+                $t { builder: self.builder.clone(), wires: self.builder.word(self.builder.with(f)) }
+            }
+
+            fn bit<F: FnOnce(&mut CircuitBuilder) -> Wire>(&self, f: F) -> Bit {
+                Bit { builder: self.builder.clone(), wire: self.builder.with(f) }
+            }
+
+            /// Bit `i`, counting from the least significant bit.
+            pub fn get(&self, i: usize) -> Bit {
+                Bit { builder: self.builder.clone(), wire: self.wires[i] }
+            }
+
+            pub fn lt(&self, other: &Self) -> Bit {
+                self.bit(|b| $lt(b, &self.wires, &other.wires))
+            }
+
+            pub fn gt(&self, other: &Self) -> Bit {
+                other.lt(self)
+            }
+
+            pub fn le(&self, other: &Self) -> Bit {
+                !other.lt(self)
+            }
+
+            pub fn ge(&self, other: &Self) -> Bit {
+                !self.lt(other)
+            }
+
+            pub fn eq(&self, other: &Self) -> Bit {
+                self.bit(|b| gadgets::equal(b, &self.wires, &other.wires))
+            }
+
+            pub fn ne(&self, other: &Self) -> Bit {
+                !self.eq(other)
+            }
+
+            pub fn min(&self, other: &Self) -> Self {
+                self.lt(other).select(self, other)
+            }
+
+            pub fn max(&self, other: &Self) -> Self {
+                self.lt(other).select(other, self)
+            }
+        }
+
+        impl<const N: usize> Add for &$t<N> {
+            type Output = $t<N>;
+
+            fn add(self, rhs: Self) -> $t<N> {
+                self.word(|b| gadgets::add(b, &self.wires, &rhs.wires))
+            }
+        }
+
+        impl<const N: usize> Sub for &$t<N> {
+            type Output = $t<N>;
+
+            fn sub(self, rhs: Self) -> $t<N> {
+                self.word(|b| gadgets::sub(b, &self.wires, &rhs.wires))
+            }
+        }
+
+        // The low half of the product is the same for signed and unsigned operands.
+        impl<const N: usize> Mul for &$t<N> {
+            type Output = $t<N>;
+
+            fn mul(self, rhs: Self) -> $t<N> {
+                self.word(|b| gadgets::mul(b, &self.wires, &rhs.wires))
+            }
+        }
+
+        impl<const N: usize> BitAnd for &$t<N> {
+            type Output = $t<N>;
+
+            fn bitand(self, rhs: Self) -> $t<N> {
+                self.word(|b| bitwise(b, &self.wires, &rhs.wires, CircuitBuilder::and))
+            }
+        }
+
+        impl<const N: usize> BitXor for &$t<N> {
+            type Output = $t<N>;
+
+            fn bitxor(self, rhs: Self) -> $t<N> {
+                self.word(|b| bitwise(b, &self.wires, &rhs.wires, CircuitBuilder::xor))
+            }
+        }
+
+        impl<const N: usize> BitOr for &$t<N> {
+            type Output = $t<N>;
+
+            fn bitor(self, rhs: Self) -> $t<N> {
+                self.word(|b| bitwise(b, &self.wires, &rhs.wires, CircuitBuilder::or))
+            }
+        }
+
+        impl<const N: usize> Not for &$t<N> {
+            type Output = $t<N>;
+
+            fn not(self) -> $t<N> {
+                self.word(|b| self.wires.iter().map(|&a| b.not(a)).collect())
+            }
+        }
+
+        impl<const N: usize> Shl<usize> for &$t<N> {
+            type Output = $t<N>;
+
+            fn shl(self, amount: usize) -> $t<N> {
+                self.word(|b| shift_constant(b, &self.wires, amount, true, None))
+            }
+        }
+
+        impl<const N: usize> Shr<usize> for &$t<N> {
+            type Output = $t<N>;
+
+            fn shr(self, amount: usize) -> $t<N> {
+                self.word(|b| $shr(b, &self.wires, amount))
+            }
+        }
+
+        /// Shifts by a secret amount using a barrel shifter.
+        impl<'a, const N: usize, const M: usize> Shl<&'a Uint<M>> for &'a $t<N> {
+            type Output = $t<N>;
+
+            fn shl(self, amount: &'a Uint<M>) -> $t<N> {
+                self.word(|b| gadgets::shift_left(b, &self.wires, &amount.wires))
+            }
+        }
+
+        /// Shifts by a secret amount using a barrel shifter.
+        impl<'a, const N: usize, const M: usize> Shr<&'a Uint<M>> for &'a $t<N> {
+            type Output = $t<N>;
+
+            fn shr(self, amount: &'a Uint<M>) -> $t<N> {
+                self.word(|b| $shr_secret(b, &self.wires, &amount.wires))
+            }
+        }
+
+        impl<const N: usize> Shl<usize> for $t<N> {
+            type Output = $t<N>;
+
+            fn shl(self, amount: usize) -> $t<N> {
+                &self << amount
+            }
+        }
+
+        impl<const N: usize> Shr<usize> for $t<N> {
+            type Output = $t<N>;
+
+            fn shr(self, amount: usize) -> $t<N> {
+                &self >> amount
+            }
+        }
+
+        impl<const N: usize, const M: usize> Shl<Uint<M>> for $t<N> {
+            type Output = $t<N>;
+
+            fn shl(self, amount: Uint<M>) -> $t<N> {
+                &self << &amount
+            }
+        }
+
+        impl<const N: usize, const M: usize> Shr<Uint<M>> for $t<N> {
+            type Output = $t<N>;
+
+            fn shr(self, amount: Uint<M>) -> $t<N> {
+                &self >> &amount
+            }
+        }
+
+        forward_owned!(impl<N> Add::add for $t<N>);
+        forward_owned!(impl<N> Sub::sub for $t<N>);
+        forward_owned!(impl<N> Mul::mul for $t<N>);
+        forward_owned!(impl<N> BitAnd::bitand for $t<N>);
+        forward_owned!(impl<N> BitXor::bitxor for $t<N>);
+        forward_owned!(impl<N> BitOr::bitor for $t<N>);
+        forward_owned!(impl<N> Not::not for $t<N>, unary);
+    };
+}
+
+impl_word!(Uint, gadgets::less_than, |b: &mut CircuitBuilder, x: &[Wire], amount| {
+    shift_constant(b, x, amount, false, None)
+}, gadgets::shift_right);
+impl_word!(Int, gadgets::signed_less_than, |b: &mut CircuitBuilder, x: &[Wire], amount| {
+    shift_constant(b, x, amount, false, x.last().copied())
+}, gadgets::shift_right_arithmetic);
+
+impl<const N: usize> Uint<N> {
+    /// Truncates or zero extends to `M` bits.
+    pub fn resize<const M: usize>(&self) -> Uint<M> {
+        let zero = if M > N { Some(self.builder.bit(false).wire) } else { None };
+        let wires = (0..M).map(|i| self.wires.get(i).copied().or(zero).unwrap()).collect();
+        Uint { builder: self.builder.clone(), wires: self.builder.word(wires) }
+    }
+
+    /// Reinterprets the bits as a two's complement integer.
+    pub fn as_int(&self) -> Int<N> {
+        Int { builder: self.builder.clone(), wires: self.wires }
+    }
+
+    /// Quotient and remainder; dividing by zero gives all ones and `self`.
+    pub fn div_rem(&self, other: &Self) -> (Self, Self) {
+        let (q, r) = self.builder.with(|b| gadgets::div_rem(b, &self.wires, &other.wires));
+        (Self::from_wires(&self.builder, &q), Self::from_wires(&self.builder, &r))
+    }
+
+    pub fn count_ones(&self) -> Uint<N> {
+        self.word(|b| {
+            let mut count = gadgets::popcount(b, &self.wires);
+            count.resize_with(N, || b.constant(false));
+            count.truncate(N);
+            count
+        })
+    }
+}
+
+impl<const N: usize> Div for &Uint<N> {
+    type Output = Uint<N>;
+
+    fn div(self, rhs: Self) -> Uint<N> {
+        self.div_rem(rhs).0
+    }
+}
+
+impl<const N: usize> Rem for &Uint<N> {
+    type Output = Uint<N>;
+
+    fn rem(self, rhs: Self) -> Uint<N> {
+        self.div_rem(rhs).1
+    }
+}
+
+forward_owned!(impl<N> Div::div for Uint<N>);
+forward_owned!(impl<N> Rem::rem for Uint<N>);
+
+impl<const N: usize> Int<N> {
+    /// Truncates or sign extends to `M` bits.
+    ///
+    /// # Panics
+    ///
+    /// If `N` is zero, since there is no sign bit to extend.
+    pub fn resize<const M: usize>(&self) -> Int<M> {
+        assert!(N > 0, "cannot resize a zero-width integer");
+        let wires = (0..M).map(|i| self.wires[i.min(N - 1)]).collect();
+        Int { builder: self.builder.clone(), wires: self.builder.word(wires) }
+    }
+
+    /// Reinterprets the bits as an unsigned integer.
+    pub fn as_uint(&self) -> Uint<N> {
+        Uint { builder: self.builder.clone(), wires: self.wires }
+    }
+}
+
+impl<const N: usize> Neg for &Int<N> {
+    type Output = Int<N>;
+
+    fn neg(self) -> Int<N> {
+        self.word(|b| gadgets::negate(b, &self.wires))
+    }
+}
+
+forward_owned!(impl<N> Neg::neg for Int<N>, unary);
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, thread_rng};
+
+    use crate::circuit::{Bit, Int, TypedBuilder, Uint};
+    use crate::garbled_circuit::GarbledCircuit;
+    use crate::garbled_circuit::half_gates::HalfGates;
+    use crate::util::{bits_to_u64, u64_to_bits};
+
+    fn word(bits: &[bool]) -> u64 {
+        bits.iter().rev().fold(0, |acc, &bit| acc << 1 | bit as u64)
+    }
+
+    #[test]
+    fn test_uint() {
+        let builder = TypedBuilder::new();
+        let x: Uint<64> = builder.input();
+        let y: Uint<64> = builder.input();
+        let three = builder.uint::<64>(3);
+        builder.output(&((&x + &y) * three - &x));
+        builder.output(&(&x / &y));
+        builder.output(&(&x % &y));
+        builder.output(&((&x & &y) | !(&x ^ &y)));
+        builder.output(&((&x << 3) ^ (&y >> 5)));
+        builder.output(&(&x << &y.resize::<6>()));
+        builder.output(&(&x >> &y.resize::<6>()));
+        builder.output(&x.max(&y));
+        builder.output(&x.lt(&y));
+        builder.output(&x.eq(&x));
+        let circuit = builder.build();
+        assert!(circuit.verify_topology());
+        assert_eq!(circuit.input_groups, [64, 64]);
+
+        let mut rng = thread_rng();
+        for _ in 0..32 {
+            let (x, y) = (rng.gen::<u64>(), rng.gen::<u64>() >> rng.gen_range(0..64) | 1);
+            let output = circuit.evaluate([u64_to_bits(x), u64_to_bits(y)].concat());
+            let words = output.chunks(64).take(8).map(bits_to_u64).collect::<Vec<_>>();
+            assert_eq!(words, [
+                x.wrapping_add(y).wrapping_mul(3).wrapping_sub(x),
+                x / y,
+                x % y,
+                (x & y) | !(x ^ y),
+                (x << 3) ^ (y >> 5),
+                x << (y & 63),
+                x >> (y & 63),
+                x.max(y),
+            ]);
+            assert_eq!(output[8 * 64..], [x < y, true]);
+        }
+    }
+
+    #[test]
+    fn test_int() {
+        let builder = TypedBuilder::new();
+        let x: Int<16> = builder.input();
+        let y: Int<16> = builder.input();
+        let negative = x.lt(&builder.int(0));
+        builder.output(&negative.select(&-&x, &x));
+        builder.output(&(&x >> 3));
+        builder.output(&(&x >> &y.as_uint().resize::<4>()));
+        builder.output(&x.min(&y));
+        builder.output(&(x.le(&y) & y.ge(&x)));
+        let circuit = builder.build();
+
+        for &(x, y) in &[(0i16, 0i16), (-5, 3), (7, -100), (i16::MIN, i16::MAX), (-1, -1)] {
+            let input = [u64_to_bits(x as u16 as u64)[..16].to_vec(), u64_to_bits(y as u16 as u64)[..16].to_vec()];
+            let output = circuit.evaluate(input.concat());
+            let words = output.chunks(16).take(4).map(|w| word(w) as u16 as i16).collect::<Vec<_>>();
+            assert_eq!(words, [x.wrapping_abs(), x >> 3, x >> (y & 15), x.min(y)]);
+            assert_eq!(output[64], x <= y);
+        }
+    }
+
+    #[test]
+    fn test_shift_without_fill() {
+        let builder = TypedBuilder::new();
+        let x: Uint<8> = builder.input();
+        builder.output(&(&x << 0));
+        builder.output(&(&x >> 0));
+        assert_eq!(builder.build().stats().constant_count, 0);
+    }
+
+    #[test]
+    fn test_garble() {
+        let builder = TypedBuilder::new();
+        let a: Bit = builder.input();
+        let x: Uint<8> = builder.input();
+        let y: Uint<8> = builder.input();
+        builder.output(&a.select(&(&x * &y), &(&x + &y)));
+        let circuit = builder.build();
+
+        let (gc, e, d) = HalfGates::garble_circuit(&circuit);
+        for &(a, x, y) in &[(true, 12u8, 13u8), (false, 200, 100)] {
+            let input = [vec![a], u64_to_bits(x as u64)[..8].to_vec(), u64_to_bits(y as u64)[..8].to_vec()].concat();
+            let expected = if a { x.wrapping_mul(y) } else { x.wrapping_add(y) };
+            assert_eq!(word(&gc.garble_compute(&e, &d, &input)), expected as u64);
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use std::fmt;
+
+use crate::circuit::{Circuit, Gate};
+
+/// Kind of problem found by [`Circuit::validate`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    /// The wire is outside of `0..input_length + gates.len()`.
+    OutOfRange,
+    /// The gate reads a wire that no earlier gate has written.
+    Unassigned,
+    /// The gate writes a wire that an earlier gate already wrote.
+    Reassigned,
+    /// The gate writes an input wire.
+    InputOverwritten,
+    /// An output wire is never written.
+    OutputUnassigned,
+    /// `output_length` exceeds the number of wires; `wire` holds the number of wires.
+    TooManyOutputs,
+    /// The input groups do not add up to `input_length`; `wire` holds their sum, saturated
+    /// at `usize::MAX`.
+    InputGroupsMismatch,
+    /// The output groups do not add up to `output_length`; `wire` holds their sum, saturated
+    /// at `usize::MAX`.
+    OutputGroupsMismatch,
+}
+
+/// A single problem with the topology of a circuit.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// Index into `gates` of the offending gate, if the problem is tied to one.
+    pub gate: Option<usize>,
+    pub wire: usize,
+    pub kind: DiagnosticKind,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(gate) = self.gate {
+            write!(f, "gate {}: ", gate)?;
+        }
+        match self.kind {
+            DiagnosticKind::OutOfRange => write!(f, "wire {} is out of range", self.wire),
+            DiagnosticKind::Unassigned => write!(f, "wire {} is read before it is written", self.wire),
+            DiagnosticKind::Reassigned => write!(f, "wire {} is written more than once", self.wire),
+            DiagnosticKind::InputOverwritten => write!(f, "input wire {} is overwritten", self.wire),
+            DiagnosticKind::OutputUnassigned => write!(f, "output wire {} is never written", self.wire),
+            DiagnosticKind::TooManyOutputs => write!(f, "more outputs than the {} wires", self.wire),
+            DiagnosticKind::InputGroupsMismatch => write!(f, "input groups add up to {} wires", self.wire),
+            DiagnosticKind::OutputGroupsMismatch => write!(f, "output groups add up to {} wires", self.wire),
+        }
+    }
+}
+
+impl Circuit {
+    /// Checks that every gate only reads wires that are already populated and that every
+    /// non-input wire is written exactly once, and that the groups add up to the input and
+    /// output lengths, returning all problems found.
+    ///
+    /// Unlike [`Circuit::evaluate`] this never panics, whatever the contents of the circuit.
+    /// Evaluation, statistics and garbling assume a circuit without diagnostics.
+    /// Memory use is proportional to the number of gates rather than to `input_length`.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let wire_count = self.input_length.saturating_add(self.gates.len());
+        // Only gate outputs are tracked, inputs are always populated.
+        let mut written = vec![false; self.gates.len()];
+        let mut diagnostics = Vec::new();
+
+        let group_sum = |groups: &[usize]| groups.iter().fold(0usize, |sum, &size| sum.saturating_add(size));
+        for (groups, length, kind) in [
+            (&self.input_groups, self.input_length, DiagnosticKind::InputGroupsMismatch),
+            (&self.output_groups, self.output_length, DiagnosticKind::OutputGroupsMismatch),
+        ] {
+            let sum = group_sum(groups);
+            if sum != length {
+                diagnostics.push(Diagnostic { gate: None, wire: sum, kind });
+            }
+        }
+
+        for (i, &(output, gate)) in self.gates.iter().enumerate() {
+            let mut read = |wire: usize| {
+                if wire < self.input_length {
+                    return;
+                }
+                let kind = match written.get(wire - self.input_length) {
+                    None => DiagnosticKind::OutOfRange,
+                    Some(false) => DiagnosticKind::Unassigned,
+                    Some(true) => return,
+                };
+                diagnostics.push(Diagnostic { gate: Some(i), wire, kind });
+            };
+            match gate {
+                Gate::Eq(_) => {}
+                Gate::EqW(a) | Gate::Inv(a) => read(a),
+                Gate::And(a, b) | Gate::Xor(a, b) => {
+                    read(a);
+                    read(b);
+                }
+            }
+
+            let kind = if output >= wire_count {
+                DiagnosticKind::OutOfRange
+            } else if output < self.input_length {
+                DiagnosticKind::InputOverwritten
+            } else if written[output - self.input_length] {
+                DiagnosticKind::Reassigned
+            } else {
+                written[output - self.input_length] = true;
+                continue;
+            };
+            diagnostics.push(Diagnostic { gate: Some(i), wire: output, kind });
+        }
+
+        if self.output_length > wire_count {
+            diagnostics.push(Diagnostic { gate: None, wire: wire_count, kind: DiagnosticKind::TooManyOutputs });
+        }
+        // Outputs that are inputs are always populated.
+        let outputs = wire_count.saturating_sub(self.output_length).max(self.input_length)..wire_count;
+        diagnostics.extend(outputs
+            .filter(|&wire| !written[wire - self.input_length])
+            .map(|wire| Diagnostic { gate: None, wire, kind: DiagnosticKind::OutputUnassigned }));
+        diagnostics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::circuit::{Circuit, Diagnostic, DiagnosticKind, Gate};
+
+    #[test]
+    fn test_bundled_circuits() {
+        for text in [
+            include_str!("../../circuits/adder64.txt"),
+            include_str!("../../circuits/aes_128.txt"),
+            include_str!("../../circuits/sha256.txt"),
+            include_str!("../../circuits/zero_equal.txt"),
+        ] {
+            let circuit: Circuit = text.parse().unwrap();
+            assert_eq!(circuit.validate(), []);
+        }
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let circuit = Circuit {
+            input_length: 2,
+            output_length: 2,
+            input_groups: vec![2],
+            output_groups: vec![2],
+            gates: vec![
+                (2, Gate::And(0, 3)),
+                (1, Gate::Inv(0)),
+                (2, Gate::Xor(0, 9)),
+                (7, Gate::Eq(true)),
+            ],
+        };
+        let diagnostic = |gate, wire, kind| Diagnostic { gate, wire, kind };
+        assert_eq!(circuit.validate(), [
+            diagnostic(Some(0), 3, DiagnosticKind::Unassigned),
+            diagnostic(Some(1), 1, DiagnosticKind::InputOverwritten),
+            diagnostic(Some(2), 9, DiagnosticKind::OutOfRange),
+            diagnostic(Some(2), 2, DiagnosticKind::Reassigned),
+            diagnostic(Some(3), 7, DiagnosticKind::OutOfRange),
+            diagnostic(None, 4, DiagnosticKind::OutputUnassigned),
+            diagnostic(None, 5, DiagnosticKind::OutputUnassigned),
+        ]);
+        assert!(!circuit.verify_topology());
+    }
+
+    #[test]
+    fn test_bad_lengths() {
+        let circuit = Circuit {
+            input_length: usize::MAX,
+            output_length: 1,
+            input_groups: vec![1],
+            output_groups: vec![2],
+            gates: vec![(usize::MAX, Gate::And(0, usize::MAX - 1))],
+        };
+        let diagnostic = |gate, wire, kind| Diagnostic { gate, wire, kind };
+        assert_eq!(circuit.validate(), [
+            diagnostic(None, 1, DiagnosticKind::InputGroupsMismatch),
+            diagnostic(None, 2, DiagnosticKind::OutputGroupsMismatch),
+            diagnostic(Some(0), usize::MAX, DiagnosticKind::OutOfRange),
+        ]);
+    }
+
+    #[test]
+    fn test_too_many_outputs() {
+        let circuit: Circuit = "1 3\n1 2\n1 1\n\n2 1 0 1 2 AND\n".parse().unwrap();
+        let circuit = Circuit { output_length: 10, output_groups: vec![10], ..circuit };
+        assert_eq!(circuit.validate(), [
+            Diagnostic { gate: None, wire: 3, kind: DiagnosticKind::TooManyOutputs },
+        ]);
+        assert!(!circuit.verify_topology());
+    }
+}
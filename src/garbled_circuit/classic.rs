@@ -8,19 +8,24 @@ use sha2::{Digest, Sha256};
 use sha2::digest::Update;
 
 use crate::circuit::{Circuit, Gate};
-use crate::garbled_circuit::{GarbledCircuit, GarbledDecoder, GarbledEncoder};
+use crate::garbled_circuit::{GarbledCircuit, GarbledDecoder, GarbledEncoder, garbled_size, Garbling};
 
-const H_BYTES: usize = 256 / 8;
-const LABEL_BYTES: usize = H_BYTES / 2;
+pub(super) const H_BYTES: usize = 256 / 8;
+pub(super) const LABEL_BYTES: usize = H_BYTES / 2;
 
 pub struct ClassicEncoder {
-    inner: Vec<(Vec<u8>, Vec<u8>)>,
+    pub(super) inner: Vec<(Vec<u8>, Vec<u8>)>,
+    pub(super) groups: Vec<usize>,
 }
 
 impl GarbledEncoder<Vec<u8>> for ClassicEncoder {
-    fn encode(&self, input: &[bool]) -> Vec<Vec<u8>> {
+    fn groups(&self) -> &[usize] {
+        &self.groups
+    }
+
+    fn encode_at(&self, offset: usize, input: &[bool]) -> Vec<Vec<u8>> {
         input.iter().cloned()
-            .zip(&self.inner)
+            .zip(&self.inner[offset..offset + input.len()])
             .map(|(b, e)| {
                 if b { &e.1 } else { &e.0 }.clone()
             })
@@ -29,13 +34,18 @@ impl GarbledEncoder<Vec<u8>> for ClassicEncoder {
 }
 
 pub struct ClassicDecoder {
-    inner: Vec<(Vec<u8>, Vec<u8>)>,
+    pub(super) inner: Vec<(Vec<u8>, Vec<u8>)>,
+    pub(super) groups: Vec<usize>,
 }
 
 impl GarbledDecoder<Vec<u8>> for ClassicDecoder {
-    fn decode(&self, input: &[Vec<u8>]) -> Vec<bool> {
+    fn groups(&self) -> &[usize] {
+        &self.groups
+    }
+
+    fn decode_at(&self, offset: usize, input: &[Vec<u8>]) -> Vec<bool> {
         input.iter()
-            .zip(&self.inner)
+            .zip(&self.inner[offset..offset + input.len()])
             .map(|(b, e)| {
                 if eq(b, &e.1) {
                     true
@@ -50,53 +60,29 @@ impl GarbledDecoder<Vec<u8>> for ClassicDecoder {
 }
 
 pub struct Classic {
-    input_length: usize,
-    output_length: usize,
-    gates: Vec<(usize, Gate)>,
-    ciphers: Vec<Vec<Vec<u8>>>,
+    garbling: Garbling<Vec<Vec<u8>>>,
 }
 
 impl GarbledCircuit<ClassicEncoder, ClassicDecoder> for Classic {
     type Label = Vec<u8>;
 
-    fn evaluate(&self, mut input: Vec<Self::Label>) -> Vec<Self::Label> {
-        assert_eq!(input.len(), self.input_length);
-
-        let mut values = {
-            input.resize(self.input_length + self.gates.len(), Default::default());
-            input
-        };
-
-        self.gates.iter().cloned()
-            .zip(&self.ciphers)
-            .for_each(|((output, gate), ciphers)| {
-                let h = match gate {
-                    Gate::Inv(a) => {
-                        let label = &values[a];
-                        hash(&[label, &output.to_be_bytes()])
-                    }
-                    Gate::And(a, b) | Gate::Xor(a, b) => {
-                        let left_label = &values[a];
-                        let right_label = &values[b];
-                        hash(&[left_label, right_label, &output.to_be_bytes()])
-                    }
-                };
-                if let Ok(Some(mut correct)) = ciphers.iter()
-                    .map(|c| xor(c, &h))
-                    .filter(|c| c[LABEL_BYTES..].iter().all(|&e| e == 0))
-                    .at_most_one() {
-                    correct.resize(LABEL_BYTES, 0);
-                    values[output] = correct;
-                } else {
-                    panic!("Too many or few correct");
-                }
-            });
-
-        values.into_iter()
-            .rev()
-            .take(self.output_length)
-            .rev()
-            .collect()
+    fn evaluate(&self, input: Vec<Self::Label>) -> Vec<Self::Label> {
+        self.garbling.evaluate(input, |i, gate, ciphers, values| {
+            let h = match gate {
+                Gate::Eq(_) => return ciphers[0].clone(),
+                Gate::EqW(_) | Gate::Inv(_) => unreachable!(),
+                Gate::And(a, b) | Gate::Xor(a, b) => hash(&[&values[a], &values[b], &i.to_be_bytes()]),
+            };
+            if let Ok(Some(mut correct)) = ciphers.iter()
+                .map(|c| xor(c, &h))
+                .filter(|c| c[LABEL_BYTES..].iter().all(|&e| e == 0))
+                .at_most_one() {
+                correct.resize(LABEL_BYTES, 0);
+                correct
+            } else {
+                panic!("Too many or few correct");
+            }
+        })
     }
 
     fn garble_circuit(circuit: &Circuit) -> (Self, ClassicEncoder, ClassicDecoder) {
@@ -110,70 +96,38 @@ impl GarbledCircuit<ClassicEncoder, ClassicDecoder> for Classic {
             })
             .collect::<Vec<_>>();
 
-        let encoding = labels.iter()
-            .cloned()
-            .take(circuit.input_length)
-            .collect::<Vec<_>>();
-
-        let decoding = labels.iter()
-            .cloned()
-            .rev()
-            .take(circuit.output_length)
-            .rev()
-            .collect::<Vec<_>>();
-
-        let ciphers = circuit.gates.iter()
-            .cloned()
-            .map(|(output, gate)| {
-                match gate {
-                    Gate::Inv(a) => {
-                        let mut c = [false, true].iter().cloned()
-                            .map(|value| {
-                                let label = if value { &labels[a].1 } else { &labels[a].0 };
-                                let h = hash(&[label, &output.to_be_bytes()]);
-                                let mut result = if value {
-                                    &labels[output].0
-                                } else {
-                                    &labels[output].1
-                                }.clone();
-                                result.resize(2 * LABEL_BYTES, 0);
-                                xor(&result, &h)
-                            })
-                            .collect::<Vec<_>>();
-                        c.shuffle(&mut OsRng);
-                        c
-                    }
-                    Gate::And(a, b) => {
-                        garble_binary(a, b, output, &labels, bool::bitand)
-                    }
-                    Gate::Xor(a, b) => {
-                        garble_binary(a, b, output, &labels, bool::bitxor)
-                    }
-                }
-            })
-            .collect::<Vec<_>>();
-
-        (
-            Classic {
-                input_length: circuit.input_length,
-                output_length: circuit.output_length,
-                gates: circuit.gates.clone(),
-                ciphers,
+        let (garbling, encoding, decoding) = Garbling::new(
+            circuit,
+            labels,
+            |label| vec![label.clone()],
+            |gate, output, tweak, labels| match gate {
+                Gate::And(a, b) => garble_binary(a, b, output, tweak, labels, bool::bitand),
+                Gate::Xor(a, b) => garble_binary(a, b, output, tweak, labels, bool::bitxor),
+                _ => unreachable!(),
             },
-            ClassicEncoder { inner: encoding },
-            ClassicDecoder { inner: decoding },
+        );
+        (
+            Classic { garbling },
+            ClassicEncoder { inner: encoding, groups: circuit.input_groups.clone() },
+            ClassicDecoder { inner: decoding, groups: circuit.output_groups.clone() },
         )
     }
+
+    fn estimate_size(circuit: &Circuit) -> usize {
+        garbled_size(circuit, LABEL_BYTES, |_, _| 4 * H_BYTES)
+    }
 }
 
-fn garble_binary<F>(a: usize, b: usize, output: usize, labels: &Vec<(Vec<u8>, Vec<u8>)>, mut f: F) -> Vec<Vec<u8>>
+/// `tweak` is the index of the gate in the garbled circuit, which stays unique after wires
+/// are mapped to slots.
+pub(super) fn garble_binary<F>(a: usize, b: usize, output: usize, tweak: usize, labels: &[(Vec<u8>, Vec<u8>)], mut f: F) -> Vec<Vec<u8>>
     where F: FnMut(bool, bool) -> bool {
     let mut c = [false, true].iter().cloned()
         .cartesian_product([false, true].iter().cloned())
         .map(|(left, right)| {
             let left_label = if left { &labels[a].1 } else { &labels[a].0 };
             let right_label = if right { &labels[b].1 } else { &labels[b].0 };
-            let h = hash(&[left_label, right_label, &output.to_be_bytes()]);
+            let h = hash(&[left_label, right_label, &tweak.to_be_bytes()]);
             let mut result = if f(left, right) {
                 &labels[output].1
             } else {
@@ -187,7 +141,7 @@ fn garble_binary<F>(a: usize, b: usize, output: usize, labels: &Vec<(Vec<u8>, Ve
     c
 }
 
-fn hash(input: &[&[u8]]) -> Vec<u8> {
+pub(super) fn hash(input: &[&[u8]]) -> Vec<u8> {
     let mut hasher = Sha256::new();
     input.iter()
         .cloned()
@@ -195,7 +149,7 @@ fn hash(input: &[&[u8]]) -> Vec<u8> {
     hasher.finalize().to_vec()
 }
 
-fn xor(a: &[u8], b: &[u8]) -> Vec<u8> {
+pub(super) fn xor(a: &[u8], b: &[u8]) -> Vec<u8> {
     assert_eq!(a.len(), b.len());
     a.iter()
         .zip(b)
@@ -214,5 +168,13 @@ fn eq(a: &[u8], b: &[u8]) -> bool {
 mod tests {
     use crate::test_garbled_circuit;
 
+    #[test]
+    fn test_no_inversions() {
+        let circuit: Circuit = include_str!("../../circuits/aes_128.txt").parse().unwrap();
+        let (gc, _, _) = Classic::garble_circuit(&circuit);
+        assert!(gc.garbling.gates.iter().all(|&(_, gate)| !matches!(gate, Gate::Inv(_) | Gate::EqW(_))));
+        assert_eq!(gc.garbling.gates.len(), circuit.stats().and_count + circuit.stats().xor_count);
+    }
+
     test_garbled_circuit!(Classic);
 }
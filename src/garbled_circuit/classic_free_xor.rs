@@ -0,0 +1,102 @@
+use std::ops::BitAnd;
+
+use itertools::Itertools;
+use rand::RngCore;
+use rand::rngs::OsRng;
+
+use crate::circuit::{Circuit, Gate};
+use crate::garbled_circuit::{GarbledCircuit, garbled_size, Garbling};
+use crate::garbled_circuit::classic::{ClassicDecoder, ClassicEncoder, garble_binary, H_BYTES, hash, LABEL_BYTES, xor};
+
+/// [`Classic`](crate::garbled_circuit::classic::Classic) with the free-XOR technique of
+/// Kolesnikov and Schneider: the two labels of every wire differ by a global offset, so the
+/// output labels of an XOR gate are the XOR of its input labels and need no ciphertexts.
+pub struct ClassicFreeXor {
+    /// Empty for XOR gates.
+    garbling: Garbling<Vec<Vec<u8>>>,
+}
+
+impl GarbledCircuit<ClassicEncoder, ClassicDecoder> for ClassicFreeXor {
+    type Label = Vec<u8>;
+
+    fn evaluate(&self, input: Vec<Self::Label>) -> Vec<Self::Label> {
+        self.garbling.evaluate(input, |i, gate, ciphers, values| match gate {
+            Gate::Eq(_) => ciphers[0].clone(),
+            Gate::EqW(_) | Gate::Inv(_) => unreachable!(),
+            Gate::Xor(a, b) => xor(&values[a], &values[b]),
+            Gate::And(a, b) => {
+                let h = hash(&[&values[a], &values[b], &i.to_be_bytes()]);
+                if let Ok(Some(mut correct)) = ciphers.iter()
+                    .map(|c| xor(c, &h))
+                    .filter(|c| c[LABEL_BYTES..].iter().all(|&e| e == 0))
+                    .at_most_one() {
+                    correct.resize(LABEL_BYTES, 0);
+                    correct
+                } else {
+                    panic!("Too many or few correct");
+                }
+            }
+        })
+    }
+
+    fn garble_circuit(circuit: &Circuit) -> (Self, ClassicEncoder, ClassicDecoder) {
+        let mut delta = vec![0u8; LABEL_BYTES];
+        OsRng.fill_bytes(&mut delta);
+
+        // Labels of XOR outputs are replaced while garbling. Swapping the labels of an
+        // inversion keeps them `delta` apart.
+        let labels = (0..circuit.input_length + circuit.gates.len())
+            .map(|_| {
+                let mut label0 = vec![0u8; LABEL_BYTES];
+                OsRng.fill_bytes(&mut label0);
+                let label1 = xor(&label0, &delta);
+                (label0, label1)
+            })
+            .collect::<Vec<_>>();
+
+        let (garbling, encoding, decoding) = Garbling::new(
+            circuit,
+            labels,
+            |label| vec![label.clone()],
+            |gate, output, tweak, labels| match gate {
+                Gate::And(a, b) => garble_binary(a, b, output, tweak, labels, bool::bitand),
+                Gate::Xor(a, b) => {
+                    let label0 = xor(&labels[a].0, &labels[b].0);
+                    let label1 = xor(&label0, &delta);
+                    labels[output] = (label0, label1);
+                    vec![]
+                }
+                _ => unreachable!(),
+            },
+        );
+        (
+            ClassicFreeXor { garbling },
+            ClassicEncoder { inner: encoding, groups: circuit.input_groups.clone() },
+            ClassicDecoder { inner: decoding, groups: circuit.output_groups.clone() },
+        )
+    }
+
+    fn estimate_size(circuit: &Circuit) -> usize {
+        garbled_size(circuit, LABEL_BYTES, |_, gate| match gate {
+            Gate::And(_, _) => 4 * H_BYTES,
+            _ => 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_garbled_circuit;
+
+    #[test]
+    fn test_xor_is_free() {
+        let circuit: Circuit = include_str!("../../circuits/aes_128.txt").parse().unwrap();
+        let (gc, _, _) = ClassicFreeXor::garble_circuit(&circuit);
+        assert!(gc.garbling.gates.iter()
+            .zip(&gc.garbling.ciphers)
+            .all(|(&(_, gate), ciphers)| matches!(gate, Gate::Xor(_, _)) == ciphers.is_empty()));
+        assert_eq!(ClassicFreeXor::estimate_size(&circuit), 4 * 32 * circuit.stats().and_count);
+    }
+
+    test_garbled_circuit!(ClassicFreeXor);
+}
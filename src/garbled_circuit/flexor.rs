@@ -0,0 +1,224 @@
+use std::convert::TryInto;
+use std::ops::BitAnd;
+
+use rand::Rng;
+use rand::rngs::OsRng;
+
+use crate::circuit::{Circuit, Gate, Resolved};
+use crate::garbled_circuit::{GarbledCircuit, garbled_size, Garbling};
+use crate::garbled_circuit::classic::{hash, LABEL_BYTES};
+use crate::garbled_circuit::grr2::{self, garble_binary, Grr2Decoder, Grr2Encoder, permute_bit};
+
+/// Garbled material of a single gate.
+enum Cipher {
+    /// Revealed label of a public output.
+    Label(u128),
+    /// AND gate garbled like [`Grr2`](crate::garbled_circuit::grr2::Grr2).
+    And(grr2::Cipher),
+    /// Translations of the inputs of an XOR gate to the offset of its output.
+    Translate(Option<u128>, Option<u128>),
+}
+
+/// FleXOR of Kolesnikov, Mohassel and Rosulek. Every wire has a level, and the two labels of
+/// an aligned wire differ by the offset of its level. XOR gates whose inputs are aligned on the
+/// level of the output are free, other inputs are first translated to that offset with one
+/// ciphertext each.
+///
+/// AND gates are garbled with the two rows of [`Grr2`](crate::garbled_circuit::grr2::Grr2),
+/// whose output labels are unrelated, so AND outputs are not aligned and are translated by
+/// every XOR gate reading them. No offset is used circularly as long as AND outputs are on a
+/// level above their inputs and XOR outputs on a level no lower than theirs. Within these
+/// bounds every wire takes the lowest level, which is the one that needs the fewest
+/// translations at each XOR gate.
+pub struct FleXor {
+    garbling: Garbling<Cipher>,
+}
+
+impl FleXor {
+    /// Number of XOR gates garbled with 0, 1 and 2 ciphertexts, reported by
+    /// [`CircuitStats`](crate::circuit::CircuitStats).
+    pub(crate) fn xor_ciphertexts(circuit: &Circuit) -> [usize; 3] {
+        let levels = levels(circuit);
+        let mut counts = [0; 3];
+        for (&(output, gate), resolved) in circuit.gates.iter().zip(circuit.resolve_constants()) {
+            if let (Resolved::Gate, Gate::Xor(a, b)) = (resolved, gate) {
+                counts[(levels[a] != levels[output]) as usize + (levels[b] != levels[output]) as usize] += 1;
+            }
+        }
+        counts
+    }
+}
+
+impl GarbledCircuit<Grr2Encoder, Grr2Decoder> for FleXor {
+    type Label = u128;
+
+    fn evaluate(&self, input: Vec<Self::Label>) -> Vec<Self::Label> {
+        self.garbling.evaluate(input, |i, gate, cipher, values| match (gate, cipher) {
+            (Gate::Eq(_), &Cipher::Label(label)) => label,
+            (Gate::And(_, _), Cipher::And(cipher)) => grr2::evaluate_binary(gate, cipher, values, i),
+            (Gate::Xor(a, b), &Cipher::Translate(left, right)) => {
+                evaluate_translation(values[a], i, 0, left) ^ evaluate_translation(values[b], i, 1, right)
+            }
+            _ => unreachable!(),
+        })
+    }
+
+    fn garble_circuit(circuit: &Circuit) -> (Self, Grr2Encoder, Grr2Decoder) {
+        let levels = levels(circuit);
+        // The lowest bit of every offset is set, so the labels of a wire have different
+        // permutation bits.
+        let offsets = (0..=levels.iter().map(|level| level.level).max().unwrap_or(0))
+            .map(|_| OsRng.gen::<u128>() | 1)
+            .collect::<Vec<_>>();
+
+        // Labels of AND and XOR outputs are replaced while garbling.
+        let labels = (0..circuit.input_length + circuit.gates.len())
+            .map(|_| {
+                let label0 = OsRng.gen::<u128>();
+                (label0, label0 ^ offsets[0])
+            })
+            .collect::<Vec<_>>();
+
+        let (garbling, encoding, decoding) = Garbling::new(
+            circuit,
+            labels,
+            |&label| Cipher::Label(label),
+            |gate, output, tweak, labels| match gate {
+                Gate::And(a, b) => Cipher::And(garble_binary(a, b, output, tweak, labels, bool::bitand)),
+                Gate::Xor(a, b) => {
+                    let offset = offsets[levels[output].level];
+                    let mut left = labels[a];
+                    let left_cipher = translate(&mut left, levels[a] != levels[output], tweak, 0, offset);
+                    let mut right = labels[b];
+                    let right_cipher = translate(&mut right, levels[b] != levels[output], tweak, 1, offset);
+                    let label0 = left.0 ^ right.0;
+                    labels[output] = (label0, label0 ^ offset);
+                    Cipher::Translate(left_cipher, right_cipher)
+                }
+                _ => unreachable!(),
+            },
+        );
+        (
+            FleXor { garbling },
+            Grr2Encoder { inner: encoding, groups: circuit.input_groups.clone() },
+            Grr2Decoder {
+                inner: decoding.iter().map(|&(label0, _)| permute_bit(label0)).collect(),
+                groups: circuit.output_groups.clone(),
+            },
+        )
+    }
+
+    fn estimate_size(circuit: &Circuit) -> usize {
+        let levels = levels(circuit);
+        garbled_size(circuit, LABEL_BYTES, |output, gate| match gate {
+            // Two points and a byte holding the four permutation bits.
+            Gate::And(_, _) => 2 * LABEL_BYTES + 1,
+            Gate::Xor(a, b) => ((levels[a] != levels[output]) as usize + (levels[b] != levels[output]) as usize) * LABEL_BYTES,
+            _ => 0,
+        })
+    }
+}
+
+/// Level of a wire and whether its labels differ by the offset of that level. An XOR input
+/// needs a translation unless it equals the level of the output, which is always aligned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Level {
+    level: usize,
+    aligned: bool,
+}
+
+/// Level of every wire: inputs are aligned on level 0, AND outputs are unaligned one level
+/// above their highest input and XOR outputs are aligned on their highest input.
+fn levels(circuit: &Circuit) -> Vec<Level> {
+    let mut levels = vec![Level { level: 0, aligned: true }; circuit.input_length + circuit.gates.len()];
+    for (&(output, gate), resolved) in circuit.gates.iter().zip(circuit.resolve_constants()) {
+        levels[output] = match (resolved, gate) {
+            (Resolved::Public(_), _) => Level { level: 0, aligned: true },
+            (Resolved::Copy(a), _) | (Resolved::Invert(a), _) => levels[a],
+            (Resolved::Gate, Gate::EqW(a)) | (Resolved::Gate, Gate::Inv(a)) => levels[a],
+            (Resolved::Gate, Gate::And(a, b)) => Level {
+                level: levels[a].level.max(levels[b].level) + 1,
+                aligned: false,
+            },
+            (Resolved::Gate, Gate::Xor(a, b)) => Level {
+                level: levels[a].level.max(levels[b].level),
+                aligned: true,
+            },
+            (Resolved::Gate, Gate::Eq(_)) => unreachable!(),
+        };
+    }
+    levels
+}
+
+/// Moves the labels of an XOR input to `offset`. The label with permutation bit 0 becomes its
+/// hash, so only the other one needs a ciphertext.
+fn translate(labels: &mut (u128, u128), needed: bool, tweak: usize, side: u8, offset: u128) -> Option<u128> {
+    if !needed {
+        return None;
+    }
+    let swap = permute_bit(labels.0);
+    let (zero, one) = if swap { (labels.1, labels.0) } else { (labels.0, labels.1) };
+    let new_zero = translation_key(zero, tweak, side);
+    let new_one = new_zero ^ offset;
+    let cipher = new_one ^ translation_key(one, tweak, side);
+    *labels = if swap { (new_one, new_zero) } else { (new_zero, new_one) };
+    Some(cipher)
+}
+
+fn evaluate_translation(label: u128, tweak: usize, side: u8, cipher: Option<u128>) -> u128 {
+    match cipher {
+        None => label,
+        Some(cipher) => {
+            let key = translation_key(label, tweak, side);
+            if permute_bit(label) { cipher ^ key } else { key }
+        }
+    }
+}
+
+fn translation_key(label: u128, tweak: usize, side: u8) -> u128 {
+    let h = hash(&[&label.to_le_bytes(), &tweak.to_be_bytes(), &[side]]);
+    u128::from_le_bytes(h[..LABEL_BYTES].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::circuit::CircuitBuilder;
+    use crate::test_garbled_circuit;
+
+    #[test]
+    fn test_ciphertexts() {
+        let circuit: Circuit = include_str!("../../circuits/aes_128.txt").parse().unwrap();
+        let stats = circuit.stats();
+        let counts = stats.flexor_xor_ciphertexts;
+        assert_eq!(counts.iter().sum::<usize>(), stats.xor_count);
+        assert_eq!(FleXor::estimate_size(&circuit), 33 * stats.and_count + 16 * (counts[1] + 2 * counts[2]));
+    }
+
+    #[test]
+    fn test_mixed_ciphertexts() {
+        let mut builder = CircuitBuilder::new();
+        let a = builder.inputs(4);
+        let x = builder.and(a[0], a[1]);
+        let y = builder.and(a[2], a[3]);
+        // Both AND outputs are translated.
+        let z = builder.xor(x, y);
+        // Only x is translated to the level of z.
+        let w = builder.xor(z, x);
+        // Free.
+        let v = builder.xor(a[0], a[1]);
+        // v is translated up to the level of w.
+        let u = builder.xor(w, v);
+        builder.outputs(&[z, u]);
+        let circuit = builder.build();
+        assert_eq!(circuit.stats().flexor_xor_ciphertexts, [1, 2, 1]);
+        assert_eq!(FleXor::estimate_size(&circuit), 2 * 33 + 4 * 16);
+
+        let (gc, enc, dec) = FleXor::garble_circuit(&circuit);
+        for input in 0..16u8 {
+            let input = (0..4).map(|i| input >> i & 1 != 0).collect::<Vec<_>>();
+            assert_eq!(gc.garble_compute(&enc, &dec, &input), circuit.evaluate(input.clone()));
+        }
+    }
+
+    test_garbled_circuit!(FleXor);
+}
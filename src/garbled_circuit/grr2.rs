@@ -0,0 +1,277 @@
+use std::convert::TryInto;
+use std::ops::{BitAnd, BitXor};
+use std::sync::OnceLock;
+
+use rand::Rng;
+use rand::rngs::OsRng;
+
+use crate::circuit::{Circuit, Gate};
+use crate::garbled_circuit::{GarbledCircuit, GarbledDecoder, GarbledEncoder, garbled_size, Garbling};
+use crate::garbled_circuit::classic::{hash, LABEL_BYTES};
+
+pub struct Grr2Encoder {
+    pub(super) inner: Vec<(u128, u128)>,
+    pub(super) groups: Vec<usize>,
+}
+
+impl GarbledEncoder<u128> for Grr2Encoder {
+    fn groups(&self) -> &[usize] {
+        &self.groups
+    }
+
+    fn encode_at(&self, offset: usize, input: &[bool]) -> Vec<u128> {
+        input.iter().cloned()
+            .zip(&self.inner[offset..offset + input.len()])
+            .map(|(b, e)| if b { e.1 } else { e.0 })
+            .collect()
+    }
+}
+
+pub struct Grr2Decoder {
+    /// Permutation bit of the false label of every output.
+    pub(super) inner: Vec<bool>,
+    pub(super) groups: Vec<usize>,
+}
+
+impl GarbledDecoder<u128> for Grr2Decoder {
+    fn groups(&self) -> &[usize] {
+        &self.groups
+    }
+
+    fn decode_at(&self, offset: usize, input: &[u128]) -> Vec<bool> {
+        input.iter()
+            .zip(&self.inner[offset..offset + input.len()])
+            .map(|(&label, &p)| permute_bit(label) ^ p)
+            .collect()
+    }
+}
+
+/// Garbled material of a single gate.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Cipher {
+    /// Points of the polynomials at `X5` and `X6`, or the revealed label of a public output.
+    points: [u128; 2],
+    /// Encrypted permutation bit of the output label of every row.
+    bits: u8,
+}
+
+/// Point-and-permute with the 4-to-2 garbled row reduction of Pinkas et al. Every row of a
+/// gate hashes to a point on a polynomial over GF(2^128) whose value at zero is the output
+/// label, so two points are enough to let the evaluator interpolate it from any row:
+///
+/// * AND gates use a quadratic through the three rows with the same output, and the label of
+///   the fourth row lies on the quadratic through that row and the two sent points.
+/// * XOR gates use a line through each pair of rows with the same output and send one point
+///   of each line, ordered by the permutation bit of the output label.
+///
+/// The labels are derived from the hashes, so the lowest bit of each label is replaced by its
+/// permutation bit, which is sent encrypted alongside the points.
+pub struct Grr2 {
+    garbling: Garbling<Cipher>,
+}
+
+impl GarbledCircuit<Grr2Encoder, Grr2Decoder> for Grr2 {
+    type Label = u128;
+
+    fn evaluate(&self, input: Vec<Self::Label>) -> Vec<Self::Label> {
+        self.garbling.evaluate(input, |i, gate, cipher, values| match gate {
+            Gate::Eq(_) => cipher.points[0],
+            Gate::EqW(_) | Gate::Inv(_) => unreachable!(),
+            Gate::And(_, _) | Gate::Xor(_, _) => evaluate_binary(gate, cipher, values, i),
+        })
+    }
+
+    fn garble_circuit(circuit: &Circuit) -> (Self, Grr2Encoder, Grr2Decoder) {
+        // Labels of AND and XOR outputs are replaced while garbling.
+        let labels = (0..circuit.input_length + circuit.gates.len())
+            .map(|_| {
+                let label0 = OsRng.gen::<u128>();
+                (label0, with_permute_bit(OsRng.gen(), !permute_bit(label0)))
+            })
+            .collect::<Vec<_>>();
+
+        let (garbling, encoding, decoding) = Garbling::new(
+            circuit,
+            labels,
+            |&label| Cipher { points: [label, 0], bits: 0 },
+            |gate, output, tweak, labels| match gate {
+                Gate::And(a, b) => garble_binary(a, b, output, tweak, labels, bool::bitand),
+                Gate::Xor(a, b) => garble_binary(a, b, output, tweak, labels, bool::bitxor),
+                _ => unreachable!(),
+            },
+        );
+        (
+            Grr2 { garbling },
+            Grr2Encoder { inner: encoding, groups: circuit.input_groups.clone() },
+            Grr2Decoder {
+                inner: decoding.iter().map(|&(label0, _)| permute_bit(label0)).collect(),
+                groups: circuit.output_groups.clone(),
+            },
+        )
+    }
+
+    fn estimate_size(circuit: &Circuit) -> usize {
+        // Two points and a byte holding the four permutation bits.
+        garbled_size(circuit, LABEL_BYTES, |_, _| 2 * LABEL_BYTES + 1)
+    }
+}
+
+/// Field elements at which the sent points of the polynomials are evaluated. Rows use 1 to 4.
+const X5: u128 = 5;
+const X6: u128 = 6;
+
+/// Interpolates the output label of an AND or XOR gate from the row of the input labels.
+pub(super) fn evaluate_binary(gate: Gate, cipher: &Cipher, values: &[u128], tweak: usize) -> u128 {
+    let (a, b) = match gate {
+        Gate::And(a, b) | Gate::Xor(a, b) => (a, b),
+        _ => unreachable!(),
+    };
+    let row = 2 * permute_bit(values[a]) as usize + permute_bit(values[b]) as usize;
+    let (key, pad) = row_key(values[a], values[b], tweak);
+    let p = ((cipher.bits >> row) & 1 != 0) ^ pad;
+    let x = row_point(row);
+    let key = match gate {
+        Gate::And(_, _) => lagrange(&[(x, key), (X5, cipher.points[0]), (X6, cipher.points[1])], 0),
+        _ => lagrange(&[(x, key), (X5, cipher.points[p as usize])], 0),
+    };
+    with_permute_bit(key, p)
+}
+
+/// Derives the output labels of a gate from the hashes of its rows and returns the points the
+/// evaluator needs to interpolate them.
+pub(super) fn garble_binary<F>(a: usize, b: usize, output: usize, tweak: usize, labels: &mut [(u128, u128)], f: F) -> Cipher
+    where F: Fn(bool, bool) -> bool {
+    let mut rows = [(0, false, false); 4];
+    for &(left, right) in &[(false, false), (false, true), (true, false), (true, true)] {
+        let left_label = if left { labels[a].1 } else { labels[a].0 };
+        let right_label = if right { labels[b].1 } else { labels[b].0 };
+        let row = 2 * permute_bit(left_label) as usize + permute_bit(right_label) as usize;
+        let (key, pad) = row_key(left_label, right_label, tweak);
+        rows[row] = (key, pad, f(left, right));
+    }
+    let points_with = |value: bool| (0..4)
+        .filter(|&row| rows[row].2 == value)
+        .map(|row| (row_point(row), rows[row].0))
+        .collect::<Vec<_>>();
+
+    let p0 = OsRng.gen::<bool>();
+    let mut points = [0; 2];
+    let keys = if rows.iter().filter(|row| row.2).count() % 2 == 1 {
+        // The value of three rows, e.g. false for AND.
+        let majority = rows.iter().filter(|row| row.2).count() == 3;
+        let quadratic = points_with(majority);
+        points = [lagrange(&quadratic, X5), lagrange(&quadratic, X6)];
+        let (x, key) = points_with(!majority)[0];
+        let other = lagrange(&[(x, key), (X5, points[0]), (X6, points[1])], 0);
+        let key = lagrange(&quadratic, 0);
+        if majority { (other, key) } else { (key, other) }
+    } else {
+        let line0 = points_with(false);
+        let line1 = points_with(true);
+        points[p0 as usize] = lagrange(&line0, X5);
+        points[!p0 as usize] = lagrange(&line1, X5);
+        (lagrange(&line0, 0), lagrange(&line1, 0))
+    };
+    labels[output] = (with_permute_bit(keys.0, p0), with_permute_bit(keys.1, !p0));
+
+    let bits = rows.iter()
+        .enumerate()
+        .map(|(row, &(_, pad, value))| {
+            let label = if value { labels[output].1 } else { labels[output].0 };
+            ((permute_bit(label) ^ pad) as u8) << row
+        })
+        .fold(0, |bits, bit| bits | bit);
+    Cipher { points, bits }
+}
+
+/// Hashes the input labels of a row into a field element and a pad for the permutation bit.
+fn row_key(a: u128, b: u128, tweak: usize) -> (u128, bool) {
+    let h = hash(&[&a.to_le_bytes(), &b.to_le_bytes(), &tweak.to_be_bytes()]);
+    (u128::from_le_bytes(h[..LABEL_BYTES].try_into().unwrap()), h[LABEL_BYTES] & 1 != 0)
+}
+
+fn row_point(row: usize) -> u128 {
+    row as u128 + 1
+}
+
+pub(super) fn permute_bit(label: u128) -> bool {
+    label & 1 != 0
+}
+
+fn with_permute_bit(label: u128, p: bool) -> u128 {
+    (label & !1) | p as u128
+}
+
+/// Multiplication in GF(2^128) modulo x^128 + x^7 + x^2 + x + 1.
+fn gf_mul(mut a: u128, mut b: u128) -> u128 {
+    let mut result = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            result ^= a;
+        }
+        b >>= 1;
+        let carry = a >> 127 != 0;
+        a <<= 1;
+        if carry {
+            a ^= 0x87;
+        }
+    }
+    result
+}
+
+fn gf_inverse(a: u128) -> u128 {
+    // a^(2^128 - 2)
+    let (mut result, mut base) = (1, a);
+    for _ in 1..128 {
+        base = gf_mul(base, base);
+        result = gf_mul(result, base);
+    }
+    result
+}
+
+/// Evaluates at `t` the polynomial of lowest degree through `points`, whose x coordinates
+/// must be distinct and below 8.
+fn lagrange(points: &[(u128, u128)], t: u128) -> u128 {
+    // Differences of such x coordinates are again below 8.
+    static INVERSES: OnceLock<[u128; 8]> = OnceLock::new();
+    let inverses = INVERSES.get_or_init(|| {
+        let mut inverses = [0; 8];
+        for (x, inverse) in inverses.iter_mut().enumerate().skip(1) {
+            *inverse = gf_inverse(x as u128);
+        }
+        inverses
+    });
+
+    points.iter()
+        .enumerate()
+        .map(|(j, &(xj, yj))| {
+            points.iter()
+                .enumerate()
+                .filter(|&(m, _)| m != j)
+                .fold(yj, |y, (_, &(xm, _))| gf_mul(gf_mul(y, t ^ xm), inverses[(xj ^ xm) as usize]))
+        })
+        .fold(0, |sum, y| sum ^ y)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_garbled_circuit;
+
+    #[test]
+    fn test_field() {
+        for x in 1..8 {
+            assert_eq!(gf_mul(x, gf_inverse(x)), 1);
+        }
+        let a = OsRng.gen::<u128>();
+        let b = OsRng.gen::<u128>();
+        assert_eq!(gf_mul(gf_mul(a, b), gf_inverse(b)), a);
+
+        // A quadratic is recovered from any three of its points.
+        let points = (1..=4).map(|x| (x, gf_mul(a, gf_mul(x, x)) ^ gf_mul(b, x) ^ 7)).collect::<Vec<_>>();
+        assert_eq!(lagrange(&points[..3], 0), 7);
+        assert_eq!(lagrange(&points[1..], 0), 7);
+        assert_eq!(lagrange(&points[..3], 4), points[3].1);
+    }
+
+    test_garbled_circuit!(Grr2);
+}
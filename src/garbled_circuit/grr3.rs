@@ -0,0 +1,95 @@
+use std::ops::{BitAnd, BitXor};
+
+use rand::RngCore;
+use rand::rngs::OsRng;
+
+use crate::circuit::{Circuit, Gate};
+use crate::garbled_circuit::{GarbledCircuit, garbled_size, Garbling};
+use crate::garbled_circuit::classic::{hash, LABEL_BYTES, xor};
+use crate::garbled_circuit::point_and_permute::{permute_bit, PointAndPermuteDecoder, PointAndPermuteEncoder, random_labels};
+
+/// [`PointAndPermute`](crate::garbled_circuit::point_and_permute::PointAndPermute) with the
+/// 4-to-3 garbled row reduction of Naor, Pinkas and Sumner: the output label encrypted in the
+/// first row is the hash itself, so its ciphertext is always zero and is not sent.
+pub struct Grr3 {
+    /// Rows 1 to 3 of every AND and XOR gate, or the revealed label of a public output.
+    garbling: Garbling<Vec<Vec<u8>>>,
+}
+
+impl GarbledCircuit<PointAndPermuteEncoder, PointAndPermuteDecoder> for Grr3 {
+    type Label = Vec<u8>;
+
+    fn evaluate(&self, input: Vec<Self::Label>) -> Vec<Self::Label> {
+        self.garbling.evaluate(input, |i, gate, ciphers, values| match gate {
+            Gate::Eq(_) => ciphers[0].clone(),
+            Gate::EqW(_) | Gate::Inv(_) => unreachable!(),
+            Gate::And(a, b) | Gate::Xor(a, b) => {
+                let row = 2 * permute_bit(&values[a]) as usize + permute_bit(&values[b]) as usize;
+                let h = hash(&[&values[a], &values[b], &i.to_be_bytes()]);
+                match row {
+                    0 => h[..LABEL_BYTES].to_vec(),
+                    row => xor(&ciphers[row - 1], &h[..LABEL_BYTES]),
+                }
+            }
+        })
+    }
+
+    fn garble_circuit(circuit: &Circuit) -> (Self, PointAndPermuteEncoder, PointAndPermuteDecoder) {
+        // Labels of AND and XOR outputs are replaced while garbling.
+        let labels = (0..circuit.input_length + circuit.gates.len())
+            .map(|_| random_labels())
+            .collect::<Vec<_>>();
+
+        let (garbling, encoding, decoding) = Garbling::new(
+            circuit,
+            labels,
+            |label| vec![label.clone()],
+            |gate, output, tweak, labels| match gate {
+                Gate::And(a, b) => garble_binary(a, b, output, tweak, labels, bool::bitand),
+                Gate::Xor(a, b) => garble_binary(a, b, output, tweak, labels, bool::bitxor),
+                _ => unreachable!(),
+            },
+        );
+        (
+            Grr3 { garbling },
+            PointAndPermuteEncoder { inner: encoding, groups: circuit.input_groups.clone() },
+            PointAndPermuteDecoder::new(&decoding, circuit.output_groups.clone()),
+        )
+    }
+
+    fn estimate_size(circuit: &Circuit) -> usize {
+        garbled_size(circuit, LABEL_BYTES, |_, _| 3 * LABEL_BYTES)
+    }
+}
+
+/// Chooses the output labels of a gate so that the first row needs no ciphertext, and
+/// returns the other three rows.
+fn garble_binary<F>(a: usize, b: usize, output: usize, tweak: usize, labels: &mut [(Vec<u8>, Vec<u8>)], f: F) -> Vec<Vec<u8>>
+    where F: Fn(bool, bool) -> bool {
+    let mut rows = vec![(vec![], false); 4];
+    for &(left, right) in &[(false, false), (false, true), (true, false), (true, true)] {
+        let left_label = if left { &labels[a].1 } else { &labels[a].0 };
+        let right_label = if right { &labels[b].1 } else { &labels[b].0 };
+        let row = 2 * permute_bit(left_label) as usize + permute_bit(right_label) as usize;
+        let mut h = hash(&[left_label, right_label, &tweak.to_be_bytes()]);
+        h.truncate(LABEL_BYTES);
+        rows[row] = (h, f(left, right));
+    }
+
+    let (first, value) = rows[0].clone();
+    let mut other = vec![0u8; LABEL_BYTES];
+    OsRng.fill_bytes(&mut other);
+    other[0] = (other[0] & !1) | (!first[0] & 1);
+    labels[output] = if value { (other, first) } else { (first, other) };
+
+    rows[1..].iter()
+        .map(|(h, value)| xor(if *value { &labels[output].1 } else { &labels[output].0 }, h))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_garbled_circuit;
+
+    test_garbled_circuit!(Grr3);
+}
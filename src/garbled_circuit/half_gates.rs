@@ -3,35 +3,41 @@ use rand::RngCore;
 use rand::rngs::OsRng;
 
 use crate::circuit::{Circuit, Gate};
-use crate::garbled_circuit::{GarbledCircuit, GarbledDecoder, GarbledEncoder};
+use crate::garbled_circuit::{GarbledCircuit, GarbledDecoder, GarbledEncoder, garbled_size, Garbling};
 
 pub struct HalfGatesEncoder {
-    inner: Vec<(Block, Block)>,
+    pub(super) inner: Vec<(Block, Block)>,
+    pub(super) groups: Vec<usize>,
 }
 
 impl GarbledEncoder<Block> for HalfGatesEncoder {
-    fn encode(&self, input: &[bool]) -> Vec<Block> {
-        assert_eq!(input.len(), self.inner.len());
+    fn groups(&self) -> &[usize] {
+        &self.groups
+    }
 
-        self.inner.iter()
+    fn encode_at(&self, offset: usize, input: &[bool]) -> Vec<Block> {
+        self.inner[offset..offset + input.len()].iter()
             .zip(input)
             .map(|(e, &b)| match b {
-                false => e.0.clone(),
-                true => e.1.clone(),
+                false => e.0,
+                true => e.1,
             })
             .collect()
     }
 }
 
 pub struct HalfGatesDecoder {
-    inner: Vec<bool>,
+    pub(super) inner: Vec<bool>,
+    pub(super) groups: Vec<usize>,
 }
 
 impl GarbledDecoder<Block> for HalfGatesDecoder {
-    fn decode(&self, input: &[Block]) -> Vec<bool> {
-        assert_eq!(input.len(), self.inner.len());
+    fn groups(&self) -> &[usize] {
+        &self.groups
+    }
 
-        self.inner.iter()
+    fn decode_at(&self, offset: usize, input: &[Block]) -> Vec<bool> {
+        self.inner[offset..offset + input.len()].iter()
             .cloned()
             .zip(input)
             .map(|(d, o)| get_lsb(o) ^ d)
@@ -39,78 +45,48 @@ impl GarbledDecoder<Block> for HalfGatesDecoder {
     }
 }
 
+/// Garbled material of a single gate.
 #[derive(Debug, Copy, Clone)]
-enum GarbledGate {
-    // TODO: avoid copy instruction when removing INV.
-    Copy(usize),
-    And(usize, usize),
-    Xor(usize, usize),
+pub(super) enum GarbledGate<T> {
+    /// Publicly known label of a constant wire.
+    Const(Block),
+    And(T),
+    /// XOR gates are free.
+    Xor,
 }
 
 pub struct HalfGates {
-    input_length: usize,
-    output_length: usize,
-    gates: Vec<(usize, GarbledGate)>,
-    ciphers: Vec<(Block, Block)>,
+    garbling: Garbling<GarbledGate<(Block, Block)>>,
     key: Block,
 }
 
 impl GarbledCircuit<HalfGatesEncoder, HalfGatesDecoder> for HalfGates {
     type Label = Block;
 
-    fn evaluate(&self, mut input: Vec<Self::Label>) -> Vec<Self::Label> {
-        assert_eq!(self.input_length, input.len());
-
+    fn evaluate(&self, input: Vec<Self::Label>) -> Vec<Self::Label> {
         let block_cipher = Aes128::new(&self.key);
 
-        let mut values = {
-            input.resize(self.input_length + self.gates.len(), Default::default());
-            input
-        };
-
-        let mut and_count = 0;
-        for &(output, gate) in self.gates.iter() {
-            match gate {
-                GarbledGate::And(a, b) => {
-                    let sa = get_lsb(&values[a]);
-                    let sb = get_lsb(&values[b]);
-                    // TODO use indexes
-                    // TODO error handling for and_count
-                    let (tg, te) = &self.ciphers[and_count];
-                    // First half gate
-                    let mut ha = values[a].clone();
-                    block_cipher.encrypt_block(&mut ha);
-                    let mut wg = ha.clone();
-                    if sa {
-                        wg = xor_blocks(&wg, tg);
-                    }
-                    // Second half gate
-                    let mut hb = values[b].clone();
-                    block_cipher.encrypt_block(&mut hb);
-                    let mut we = hb.clone();
-                    if sb {
-                        we = xor_blocks(&we, &xor_blocks(&te, &values[a]));
-                    }
-
-                    values[output] = xor_blocks(&wg, &we);
-
-                    and_count += 1;
-                }
-                GarbledGate::Xor(a, b) => {
-                    values[output] = xor_blocks(&values[a], &values[b]);
+        self.garbling.evaluate(input, |index, gate, cipher, values| match (gate, cipher) {
+            (Gate::And(a, b), GarbledGate::And((tg, te))) => {
+                let sa = get_lsb(&values[a]);
+                let sb = get_lsb(&values[b]);
+                // First half gate
+                let mut wg = hash(&block_cipher, &values[a], 2 * index);
+                if sa {
+                    wg = xor_blocks(&wg, tg);
                 }
-                GarbledGate::Copy(a) => {
-                    values[output] = values[a].clone();
+                // Second half gate
+                let mut we = hash(&block_cipher, &values[b], 2 * index + 1);
+                if sb {
+                    we = xor_blocks(&we, &xor_blocks(te, &values[a]));
                 }
-            }
-        }
 
-        values.iter()
-            .rev()
-            .take(self.output_length)
-            .rev()
-            .cloned()
-            .collect()
+                xor_blocks(&wg, &we)
+            }
+            (Gate::Xor(a, b), GarbledGate::Xor) => xor_blocks(&values[a], &values[b]),
+            (Gate::Eq(_), &GarbledGate::Const(label)) => label,
+            _ => unreachable!(),
+        })
     }
 
     fn garble_circuit(circuit: &Circuit) -> (Self, HalfGatesEncoder, HalfGatesDecoder) {
@@ -129,103 +105,88 @@ impl GarbledCircuit<HalfGatesEncoder, HalfGatesDecoder> for HalfGates {
             set_lsb(&mut r, true);
             r
         };
-        let mut labels: Vec<(Block, Block)> = vec![(Default::default(), Default::default()); circuit.input_length + circuit.gates.len()];
-        for i in 0..circuit.input_length {
-            OsRng.fill_bytes(&mut labels[i].0);
-            labels[i].1 = xor_blocks(&labels[i].0, &r);
-        }
-        let encoding = labels.iter()
-            .cloned()
-            .take(circuit.input_length)
+        // Labels of AND and XOR outputs are replaced while garbling.
+        let labels = (0..circuit.input_length + circuit.gates.len())
+            .map(|_| {
+                let mut label0: Block = Default::default();
+                OsRng.fill_bytes(&mut label0);
+                (label0, xor_blocks(&label0, &r))
+            })
             .collect::<Vec<_>>();
 
-        let mut ciphers = vec![];
-        let garbled_gates = circuit.gates.iter()
-            .map(|&(output, gate)| {
-                (output, match gate {
-                    Gate::Inv(a) => {
-                        labels[output] = (labels[a].1.clone(), labels[a].0.clone());
-                        GarbledGate::Copy(a)
+        let (garbling, encoding, decoding) = Garbling::new(
+            circuit,
+            labels,
+            |&label| GarbledGate::Const(label),
+            |gate, output, tweak, labels| match gate {
+                Gate::And(a, b) => {
+                    let pa = get_lsb(&labels[a].0);
+                    let pb = get_lsb(&labels[b].0);
+                    // First half gate
+                    let h0 = hash(&block_cipher, &labels[a].0, 2 * tweak);
+                    let h1 = hash(&block_cipher, &labels[a].1, 2 * tweak);
+                    let mut tg = xor_blocks(&h0, &h1);
+                    if pb {
+                        tg = xor_blocks(&tg, &r);
                     }
-                    Gate::And(a, b) => {
-                        let pa = get_lsb(&labels[a].0);
-                        let pb = get_lsb(&labels[b].0);
-                        // TODO: indexes
-                        let _j = labels.len();
-                        let _j_prime = labels.len() + 1;
-                        // First half gate
-                        let h0 = {
-                            let mut h0 = labels[a].0.clone();
-                            block_cipher.encrypt_block(&mut h0);
-                            h0
-                        };
-                        let h1 = {
-                            let mut h1 = labels[a].1.clone();
-                            block_cipher.encrypt_block(&mut h1);
-                            h1
-                        };
-                        let mut tg = xor_blocks(&h0, &h1);
-                        if pb {
-                            tg = xor_blocks(&tg, &r);
-                        }
-                        let mut w0g = h0.clone();
-                        if pa {
-                            w0g = xor_blocks(&w0g, &tg);
-                        }
-                        // Second half gate
-                        let h0 = {
-                            let mut h0 = labels[b].0.clone();
-                            block_cipher.encrypt_block(&mut h0);
-                            h0
-                        };
-                        let h1 = {
-                            let mut h1 = labels[b].1.clone();
-                            block_cipher.encrypt_block(&mut h1);
-                            h1
-                        };
-                        let te = xor_blocks(&xor_blocks(&h0, &h1), &labels[a].0);
-                        let mut w0e = h0.clone();
-                        if pb {
-                            // TODO simplify
-                            w0e = xor_blocks(&w0e, &xor_blocks(&te, &labels[a].0));
-                        }
-                        // Combine halves
-                        labels[output].0 = xor_blocks(&w0g, &w0e);
-                        labels[output].1 = xor_blocks(&labels[output].0, &r);
-                        ciphers.push((tg, te));
-                        GarbledGate::And(a, b)
+                    let mut w0g = h0;
+                    if pa {
+                        w0g = xor_blocks(&w0g, &tg);
                     }
-                    Gate::Xor(a, b) => {
-                        labels[output].0 = xor_blocks(&labels[a].0, &labels[b].0);
-                        labels[output].1 = xor_blocks(&labels[output].0, &r);
-                        GarbledGate::Xor(a, b)
+                    // Second half gate
+                    let h0 = hash(&block_cipher, &labels[b].0, 2 * tweak + 1);
+                    let h1 = hash(&block_cipher, &labels[b].1, 2 * tweak + 1);
+                    let te = xor_blocks(&xor_blocks(&h0, &h1), &labels[a].0);
+                    let mut w0e = h0;
+                    if pb {
+                        // TODO simplify
+                        w0e = xor_blocks(&w0e, &xor_blocks(&te, &labels[a].0));
                     }
-                })
-            })
-            .collect::<Vec<_>>();
-
-        let decoding = labels.iter()
-            .rev()
-            .take(circuit.output_length)
-            .rev()
-            .map(|(w0, _)| w0[0] & 1 != 0)
-            .collect::<Vec<_>>();
-
+                    // Combine halves
+                    labels[output].0 = xor_blocks(&w0g, &w0e);
+                    labels[output].1 = xor_blocks(&labels[output].0, &r);
+                    GarbledGate::And((tg, te))
+                }
+                Gate::Xor(a, b) => {
+                    labels[output].0 = xor_blocks(&labels[a].0, &labels[b].0);
+                    labels[output].1 = xor_blocks(&labels[output].0, &r);
+                    GarbledGate::Xor
+                }
+                _ => unreachable!(),
+            },
+        );
         (
-            HalfGates {
-                input_length: circuit.input_length,
-                output_length: circuit.output_length,
-                gates: garbled_gates,
-                ciphers,
-                key,
+            HalfGates { garbling, key },
+            HalfGatesEncoder { inner: encoding, groups: circuit.input_groups.clone() },
+            HalfGatesDecoder {
+                inner: decoding.iter().map(|(w0, _)| get_lsb(w0)).collect(),
+                groups: circuit.output_groups.clone(),
             },
-            HalfGatesEncoder { inner: encoding },
-            HalfGatesDecoder { inner: decoding },
         )
     }
+
+    fn estimate_size(circuit: &Circuit) -> usize {
+        let block = Block::default().len();
+        garbled_size(circuit, block, |_, gate| match gate {
+            Gate::And(_, _) => 2 * block,
+            _ => 0,
+        })
+    }
 }
 
-fn xor_blocks(a: &Block, b: &Block) -> Block {
+/// Tweakable hash from fixed-key AES, `π(x ^ tweak) ^ x ^ tweak`. Each half gate of a gate
+/// uses its own tweak.
+pub(super) fn hash(block_cipher: &Aes128, label: &Block, tweak: usize) -> Block {
+    let mut tweaked = *label;
+    tweaked.iter_mut()
+        .zip(&(tweak as u128).to_le_bytes())
+        .for_each(|(dst, t)| *dst ^= t);
+    let mut h = tweaked;
+    block_cipher.encrypt_block(&mut h);
+    xor_blocks(&h, &tweaked)
+}
+
+pub(super) fn xor_blocks(a: &Block, b: &Block) -> Block {
     let mut block: Block = Default::default();
     block.iter_mut()
         .zip(a.iter().zip(b.iter()))
@@ -234,12 +195,12 @@ fn xor_blocks(a: &Block, b: &Block) -> Block {
 }
 
 // TODO: consider inline
-fn get_lsb(s: &[u8]) -> bool {
+pub(super) fn get_lsb(s: &[u8]) -> bool {
     s[0] & 1 != 0
 }
 
 // TODO: consider inline
-fn set_lsb(s: &mut [u8], b: bool) {
+pub(super) fn set_lsb(s: &mut [u8], b: bool) {
     if b {
         s[0] |= 1;
     } else {
@@ -257,9 +218,9 @@ mod tests {
     fn test_lsb() {
         let mut s = [8, 9, 10];
         set_lsb(&mut s, true);
-        assert_eq!(get_lsb(&s), true);
+        assert!(get_lsb(&s));
         set_lsb(&mut s, false);
-        assert_eq!(get_lsb(&s), false);
+        assert!(!get_lsb(&s));
     }
 
     #[test]
@@ -277,5 +238,13 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_slots() {
+        let circuit: Circuit = include_str!("../../circuits/sha256.txt").parse().unwrap();
+        let (gc, _, _) = HalfGates::garble_circuit(&circuit);
+        assert!(gc.garbling.slot_count <= circuit.allocate_slots().slot_count);
+        assert!(gc.garbling.slot_count * 10 < circuit.input_length + circuit.gates.len());
+    }
+
     test_garbled_circuit!(HalfGates);
 }
@@ -1,14 +1,52 @@
-use crate::circuit::Circuit;
+use crate::circuit::{assign_slots, Circuit, Gate, group_range, Resolved};
 
 pub mod half_gates;
+pub mod three_halves;
 pub mod classic;
+pub mod classic_free_xor;
+pub mod point_and_permute;
+pub mod grr3;
+pub mod grr2;
+pub mod flexor;
 
 pub trait GarbledEncoder<T> {
-    fn encode(&self, input: &[bool]) -> Vec<T>;
+    /// Sizes of the input groups of the garbled circuit.
+    fn groups(&self) -> &[usize];
+
+    /// Encodes the input wires `offset..offset + input.len()`.
+    fn encode_at(&self, offset: usize, input: &[bool]) -> Vec<T>;
+
+    fn encode(&self, input: &[bool]) -> Vec<T> {
+        assert_eq!(input.len(), self.groups().iter().sum::<usize>());
+        self.encode_at(0, input)
+    }
+
+    /// Encodes the value of a single input group, e.g. the input of a single party.
+    fn encode_group(&self, group: usize, input: &[bool]) -> Vec<T> {
+        let range = group_range(self.groups(), group);
+        assert_eq!(input.len(), range.len());
+        self.encode_at(range.start, input)
+    }
 }
 
 pub trait GarbledDecoder<T> {
-    fn decode(&self, input: &[T]) -> Vec<bool>;
+    /// Sizes of the output groups of the garbled circuit.
+    fn groups(&self) -> &[usize];
+
+    /// Decodes the output wires `offset..offset + input.len()`.
+    fn decode_at(&self, offset: usize, input: &[T]) -> Vec<bool>;
+
+    fn decode(&self, input: &[T]) -> Vec<bool> {
+        assert_eq!(input.len(), self.groups().iter().sum::<usize>());
+        self.decode_at(0, input)
+    }
+
+    /// Decodes the value of a single output group.
+    fn decode_group(&self, group: usize, input: &[T]) -> Vec<bool> {
+        let range = group_range(self.groups(), group);
+        assert_eq!(input.len(), range.len());
+        self.decode_at(range.start, input)
+    }
 }
 
 pub trait GarbledCircuit<E: GarbledEncoder<Self::Label>, D: GarbledDecoder<Self::Label>>: Sized {
@@ -16,8 +54,17 @@ pub trait GarbledCircuit<E: GarbledEncoder<Self::Label>, D: GarbledDecoder<Self:
 
     fn evaluate(&self, input: Vec<Self::Label>) -> Vec<Self::Label>;
 
+    /// Garbles `circuit`, returning the garbled circuit with the encoder of its inputs and
+    /// the decoder of its outputs.
+    ///
+    /// # Panics
+    ///
+    /// If [`Circuit::validate`] reports problems with the circuit.
     fn garble_circuit(circuit: &Circuit) -> (Self, E, D);
 
+    /// Estimated size in bytes of the garbled material produced for `circuit`.
+    fn estimate_size(circuit: &Circuit) -> usize;
+
     fn garble_compute(&self, encoder: &E, decoder: &D, input: &[bool]) -> Vec<bool> {
         let encoded_input = encoder.encode(input);
         let encoded_output = self.evaluate(encoded_input);
@@ -25,23 +72,177 @@ pub trait GarbledCircuit<E: GarbledEncoder<Self::Label>, D: GarbledDecoder<Self:
     }
 }
 
+/// False and true label of a range of wires.
+type LabelPairs<L> = Vec<(L, L)>;
+
+/// Gates of a garbled circuit over label slots along with the garbled material of each, as
+/// built by [`Garbling::new`] for the schemes that garble AND and XOR gates one at a time.
+struct Garbling<C> {
+    input_length: usize,
+    /// Number of label slots the evaluator needs, see [`SlotAllocation`](crate::circuit::SlotAllocation).
+    slot_count: usize,
+    /// Only EQ, AND and XOR gates over slots, since copies and inversions are resolved while
+    /// garbling.
+    gates: Vec<(usize, Gate)>,
+    /// Material of every gate, e.g. the revealed label of an EQ gate.
+    ciphers: Vec<C>,
+    /// Slot holding the label of each output.
+    outputs: Vec<usize>,
+}
+
+impl<C> Garbling<C> {
+    /// Garbles `circuit` from the false and true label of every wire, and returns the labels of
+    /// the inputs and of the outputs for the encoder and decoder.
+    ///
+    /// Copies and inversions only relabel a wire, so the evaluator reads the label of the
+    /// source wire instead, and an inversion swaps the meaning of the two labels. Public values
+    /// are only materialized where the evaluator has to output them, by revealing the active
+    /// label with `reveal`. The remaining AND and XOR gates are passed to `garble_gate` over
+    /// wires, along with their output wire and the index of the gate in the garbled circuit,
+    /// which stays unique after wires are mapped to slots and serves as tweak. It may replace
+    /// the labels of the output wire.
+    ///
+    /// `circuit` must be valid, see [`Circuit::validate`].
+    fn new<L: Clone>(
+        circuit: &Circuit,
+        mut labels: LabelPairs<L>,
+        mut reveal: impl FnMut(&L) -> C,
+        mut garble_gate: impl FnMut(Gate, usize, usize, &mut [(L, L)]) -> C,
+    ) -> (Self, LabelPairs<L>, LabelPairs<L>) {
+        let wire_count = circuit.input_length + circuit.gates.len();
+        let first_output = wire_count - circuit.output_length;
+        let encoding = labels[..circuit.input_length].to_vec();
+
+        let mut alias = (0..wire_count).collect::<Vec<_>>();
+        let mut gates = Vec::with_capacity(circuit.gates.len());
+        let mut ciphers = Vec::with_capacity(circuit.gates.len());
+        for (&(output, gate), resolved) in circuit.gates.iter().zip(circuit.resolve_constants()) {
+            let (gate, c) = match (resolved, gate) {
+                (Resolved::Public(_), _) if output < first_output => continue,
+                (Resolved::Public(c), _) => (Gate::Eq(c), reveal(if c { &labels[output].1 } else { &labels[output].0 })),
+                (Resolved::Copy(a), _) | (Resolved::Gate, Gate::EqW(a)) => {
+                    labels[output] = labels[a].clone();
+                    alias[output] = alias[a];
+                    continue;
+                }
+                (Resolved::Invert(a), _) | (Resolved::Gate, Gate::Inv(a)) => {
+                    labels[output] = (labels[a].1.clone(), labels[a].0.clone());
+                    alias[output] = alias[a];
+                    continue;
+                }
+                (Resolved::Gate, Gate::Eq(_)) => unreachable!(),
+                (Resolved::Gate, gate) => {
+                    (gate.map_inputs(|wire| alias[wire]), garble_gate(gate, output, gates.len(), &mut labels))
+                }
+            };
+            gates.push((output, gate));
+            ciphers.push(c);
+        }
+
+        let decoding = labels.split_off(first_output);
+        let outputs = alias.split_off(first_output);
+        let (slots, slot_count) = assign_slots(circuit.input_length, wire_count, &gates, &outputs, |gate| gate.inputs());
+        let garbling = Garbling {
+            input_length: circuit.input_length,
+            slot_count,
+            gates: gates.into_iter()
+                .map(|(output, gate)| (slots[output], gate.map_inputs(|wire| slots[wire])))
+                .collect(),
+            ciphers,
+            outputs: outputs.iter().map(|&wire| slots[wire]).collect(),
+        };
+        (garbling, encoding, decoding)
+    }
+
+    /// Evaluates the gates on the labels of the inputs. `evaluate_gate` computes the label of
+    /// an EQ, AND or XOR gate over slots from the index of the gate, its material and the
+    /// labels in the slots.
+    fn evaluate<L: Clone + Default>(&self, mut input: Vec<L>, mut evaluate_gate: impl FnMut(usize, Gate, &C, &[L]) -> L) -> Vec<L> {
+        assert_eq!(input.len(), self.input_length);
+
+        let mut values = {
+            input.resize(self.slot_count, Default::default());
+            input
+        };
+
+        for (i, (&(output, gate), cipher)) in self.gates.iter().zip(&self.ciphers).enumerate() {
+            values[output] = evaluate_gate(i, gate, cipher, &values);
+        }
+
+        self.outputs.iter()
+            .map(|&slot| values[slot].clone())
+            .collect()
+    }
+}
+
+/// Estimated size in bytes of a [`Garbling`] of `circuit`: `label_bytes` for every revealed
+/// public output and `cost` for every AND and XOR gate, given its output wire.
+fn garbled_size(circuit: &Circuit, label_bytes: usize, mut cost: impl FnMut(usize, Gate) -> usize) -> usize {
+    let first_output = circuit.input_length + circuit.gates.len() - circuit.output_length;
+    circuit.gates.iter()
+        .zip(circuit.resolve_constants())
+        .map(|(&(output, gate), resolved)| match (resolved, gate) {
+            (Resolved::Public(_), _) if output < first_output => 0,
+            (Resolved::Public(_), _) => label_bytes,
+            (Resolved::Gate, Gate::And(_, _)) | (Resolved::Gate, Gate::Xor(_, _)) => cost(output, gate),
+            _ => 0,
+        })
+        .sum()
+}
+
+impl Circuit {
+    /// Estimated size in bytes of the garbled material `G` produces for this circuit, e.g.
+    /// `circuit.estimated_size::<HalfGates, _, _>()`.
+    pub fn estimated_size<G, E, D>(&self) -> usize
+        where G: GarbledCircuit<E, D>, E: GarbledEncoder<G::Label>, D: GarbledDecoder<G::Label> {
+        G::estimate_size(self)
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::circuit::Circuit;
+    use crate::garbled_circuit::classic::Classic;
+    use crate::garbled_circuit::classic_free_xor::ClassicFreeXor;
+    use crate::garbled_circuit::flexor::FleXor;
+    use crate::garbled_circuit::grr2::Grr2;
+    use crate::garbled_circuit::grr3::Grr3;
+    use crate::garbled_circuit::half_gates::HalfGates;
+    use crate::garbled_circuit::point_and_permute::PointAndPermute;
+    use crate::garbled_circuit::three_halves::ThreeHalves;
+
+    #[test]
+    fn test_estimated_size() {
+        // out0 = (a & b) ^ !c, out1 = a & b
+        let circuit: Circuit = "4 7\n1 3\n1 2\n\n2 1 0 1 3 AND\n1 1 2 4 INV\n2 1 3 4 5 XOR\n1 1 3 6 EQW\n"
+            .parse().unwrap();
+        assert_eq!(circuit.estimated_size::<Classic, _, _>(), 8 * 32);
+        assert_eq!(circuit.estimated_size::<ClassicFreeXor, _, _>(), 4 * 32);
+        assert_eq!(circuit.estimated_size::<PointAndPermute, _, _>(), 8 * 16);
+        assert_eq!(circuit.estimated_size::<Grr3, _, _>(), 6 * 16);
+        assert_eq!(circuit.estimated_size::<Grr2, _, _>(), 2 * 33);
+        assert_eq!(circuit.estimated_size::<FleXor, _, _>(), 33 + 2 * 16);
+        assert_eq!(circuit.estimated_size::<HalfGates, _, _>(), 32);
+        assert_eq!(circuit.estimated_size::<ThreeHalves, _, _>(), 25);
+
+        let circuit: Circuit = include_str!("../../circuits/aes_128.txt").parse().unwrap();
+        assert_eq!(circuit.estimated_size::<HalfGates, _, _>(), 6400 * 32);
+    }
+
     #[macro_export]
     macro_rules! test_garbled_circuit {
         ($gc:ty) => {
             use super::*;
             use hex_literal::hex;
-            use crate::circuit::Circuit;
-            use crate::garbled_circuit::GarbledCircuit;
-            use crate::util::{bits_to_u64, u64_to_bits, u8_to_bits, bits_to_u8};
+            use $crate::circuit::Circuit;
+            use $crate::garbled_circuit::{GarbledCircuit, GarbledDecoder, GarbledEncoder};
+            use $crate::util::{bits_to_u64, u64_to_bits, u8_to_bits, bits_to_u8};
 
             fn evaluate_u64(circuit: &Circuit, inputs: &[u64]) -> Vec<bool> {
                 let (gc, enc, dec) = <$gc>::garble_circuit(&circuit);
                 let input = inputs.iter()
                     .cloned()
-                    .map(u64_to_bits)
-                    .flatten()
+                    .flat_map(u64_to_bits)
                     .collect::<Vec<_>>();
                 gc.garble_compute(&enc, &dec, &input)
             }
@@ -56,6 +257,29 @@ mod tests {
                 assert_eq!(evaluate_u64(&circuit, &[1]), [false]);
             }
 
+            #[test]
+            fn test_extended_gates() {
+                let circuit: Circuit = "4 7\n1 2\n1 2\n\n1 1 1 2 EQ\n1 1 0 3 EQW\n1 1 3 4 NOT\n4 2 0 1 2 4 5 6 MAND\n"
+                    .parse().unwrap();
+                let (gc, enc, dec) = <$gc>::garble_circuit(&circuit);
+
+                for &(a, b) in &[(false, false), (false, true), (true, false), (true, true)] {
+                    assert_eq!(gc.garble_compute(&enc, &dec, &[a, b]), circuit.evaluate(vec![a, b]));
+                }
+            }
+
+            #[test]
+            fn test_groups() {
+                let circuit: Circuit = include_str!("../../circuits/adder64.txt").parse().unwrap();
+                let (gc, enc, dec) = <$gc>::garble_circuit(&circuit);
+
+                let mut input = enc.encode_group(0, &u64_to_bits(300));
+                input.extend(enc.encode_group(1, &u64_to_bits(5000)));
+                let output = gc.evaluate(input);
+                let range = circuit.output_range(0);
+                assert_eq!(bits_to_u64(&dec.decode_group(0, &output[range])), 5300);
+            }
+
             fn binop_u64(circuit: &Circuit, left: u64, right: u64) -> u64 {
                 bits_to_u64(&evaluate_u64(circuit, &[left, right]))
             }
@@ -127,7 +351,34 @@ mod tests {
                 let mut output = gc.garble_compute(&enc, &dec, &input);
                 output.reverse();
                 let output_bytes = output.chunks_exact(8)
-                    .map(|e| bits_to_u8(e))
+                    .map(bits_to_u8)
+                    .collect::<Vec<_>>();
+                assert_eq!(output_bytes, cph);
+            }
+
+            #[test]
+            fn test_public_input() {
+                let key: [u8; 16] = hex!("74 c9 f1 91 b9 02 f9 6c 32 24 3e 13 b3 5f 12 af");
+                let msg: [u8; 16] = hex!("80 14 bf b6 e6 00 f1 cd 5e ec ce c5 11 2c 4c f9");
+                let cph: [u8; 16] = hex!("7f 16 ae 5c 79 5b 18 86 b8 ca 08 b6 96 6a 7a 7c");
+
+                let circuit: Circuit = include_str!("../../circuits/aes_128.txt").parse().unwrap();
+                let key = key.iter().cloned()
+                    .flat_map(u8_to_bits)
+                    .rev()
+                    .collect::<Vec<_>>();
+                let public = circuit.with_public_input(0, &key);
+                assert!(<$gc>::estimate_size(&public) < <$gc>::estimate_size(&circuit));
+
+                let input = msg.iter().cloned()
+                    .flat_map(u8_to_bits)
+                    .rev()
+                    .collect::<Vec<_>>();
+                let (gc, enc, dec) = <$gc>::garble_circuit(&public);
+                let mut output = gc.garble_compute(&enc, &dec, &input);
+                output.reverse();
+                let output_bytes = output.chunks_exact(8)
+                    .map(bits_to_u8)
                     .collect::<Vec<_>>();
                 assert_eq!(output_bytes, cph);
             }
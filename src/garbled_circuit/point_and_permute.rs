@@ -0,0 +1,151 @@
+use std::ops::{BitAnd, BitXor};
+
+use rand::RngCore;
+use rand::rngs::OsRng;
+
+use crate::circuit::{Circuit, Gate};
+use crate::garbled_circuit::{GarbledCircuit, GarbledDecoder, GarbledEncoder, garbled_size, Garbling};
+use crate::garbled_circuit::classic::{hash, LABEL_BYTES, xor};
+
+pub struct PointAndPermuteEncoder {
+    pub(super) inner: Vec<(Vec<u8>, Vec<u8>)>,
+    pub(super) groups: Vec<usize>,
+}
+
+impl GarbledEncoder<Vec<u8>> for PointAndPermuteEncoder {
+    fn groups(&self) -> &[usize] {
+        &self.groups
+    }
+
+    fn encode_at(&self, offset: usize, input: &[bool]) -> Vec<Vec<u8>> {
+        input.iter().cloned()
+            .zip(&self.inner[offset..offset + input.len()])
+            .map(|(b, e)| {
+                if b { &e.1 } else { &e.0 }.clone()
+            })
+            .collect()
+    }
+}
+
+pub struct PointAndPermuteDecoder {
+    /// Permutation bit of the false label of every output.
+    pub(super) inner: Vec<bool>,
+    pub(super) groups: Vec<usize>,
+}
+
+impl PointAndPermuteDecoder {
+    /// Decoder for the given labels of the outputs.
+    pub(super) fn new(labels: &[(Vec<u8>, Vec<u8>)], groups: Vec<usize>) -> Self {
+        PointAndPermuteDecoder {
+            inner: labels.iter().map(|(label0, _)| permute_bit(label0)).collect(),
+            groups,
+        }
+    }
+}
+
+impl GarbledDecoder<Vec<u8>> for PointAndPermuteDecoder {
+    fn groups(&self) -> &[usize] {
+        &self.groups
+    }
+
+    fn decode_at(&self, offset: usize, input: &[Vec<u8>]) -> Vec<bool> {
+        input.iter()
+            .zip(&self.inner[offset..offset + input.len()])
+            .map(|(label, &p)| permute_bit(label) ^ p)
+            .collect()
+    }
+}
+
+/// [`Classic`](crate::garbled_circuit::classic::Classic) where the lowest bit of every label
+/// is a permutation bit, so the rows of every gate are ordered by the permutation bits of its
+/// inputs and the evaluator decrypts exactly one row instead of trying all four.
+pub struct PointAndPermute {
+    garbling: Garbling<Vec<Vec<u8>>>,
+}
+
+impl GarbledCircuit<PointAndPermuteEncoder, PointAndPermuteDecoder> for PointAndPermute {
+    type Label = Vec<u8>;
+
+    fn evaluate(&self, input: Vec<Self::Label>) -> Vec<Self::Label> {
+        self.garbling.evaluate(input, |i, gate, ciphers, values| match gate {
+            Gate::Eq(_) => ciphers[0].clone(),
+            Gate::EqW(_) | Gate::Inv(_) => unreachable!(),
+            Gate::And(a, b) | Gate::Xor(a, b) => {
+                let row = 2 * permute_bit(&values[a]) as usize + permute_bit(&values[b]) as usize;
+                let h = hash(&[&values[a], &values[b], &i.to_be_bytes()]);
+                xor(&ciphers[row], &h[..LABEL_BYTES])
+            }
+        })
+    }
+
+    fn garble_circuit(circuit: &Circuit) -> (Self, PointAndPermuteEncoder, PointAndPermuteDecoder) {
+        let labels = (0..circuit.input_length + circuit.gates.len())
+            .map(|_| random_labels())
+            .collect::<Vec<_>>();
+
+        let (garbling, encoding, decoding) = Garbling::new(
+            circuit,
+            labels,
+            |label| vec![label.clone()],
+            |gate, output, tweak, labels| match gate {
+                Gate::And(a, b) => garble_binary(a, b, output, tweak, labels, bool::bitand),
+                Gate::Xor(a, b) => garble_binary(a, b, output, tweak, labels, bool::bitxor),
+                _ => unreachable!(),
+            },
+        );
+        (
+            PointAndPermute { garbling },
+            PointAndPermuteEncoder { inner: encoding, groups: circuit.input_groups.clone() },
+            PointAndPermuteDecoder::new(&decoding, circuit.output_groups.clone()),
+        )
+    }
+
+    fn estimate_size(circuit: &Circuit) -> usize {
+        garbled_size(circuit, LABEL_BYTES, |_, _| 4 * LABEL_BYTES)
+    }
+}
+
+/// Two random labels with different permutation bits.
+pub(super) fn random_labels() -> (Vec<u8>, Vec<u8>) {
+    let mut label0 = vec![0u8; LABEL_BYTES];
+    OsRng.fill_bytes(&mut label0);
+    let mut label1 = vec![0u8; LABEL_BYTES];
+    OsRng.fill_bytes(&mut label1);
+    label1[0] = (label1[0] & !1) | (!label0[0] & 1);
+    (label0, label1)
+}
+
+/// Encrypts the output label of every combination of input labels in the row given by the
+/// permutation bits of the input labels.
+fn garble_binary<F>(a: usize, b: usize, output: usize, tweak: usize, labels: &[(Vec<u8>, Vec<u8>)], f: F) -> Vec<Vec<u8>>
+    where F: Fn(bool, bool) -> bool {
+    let mut rows = vec![vec![]; 4];
+    for &(left, right) in &[(false, false), (false, true), (true, false), (true, true)] {
+        let left_label = if left { &labels[a].1 } else { &labels[a].0 };
+        let right_label = if right { &labels[b].1 } else { &labels[b].0 };
+        let row = 2 * permute_bit(left_label) as usize + permute_bit(right_label) as usize;
+        let h = hash(&[left_label, right_label, &tweak.to_be_bytes()]);
+        let result = if f(left, right) { &labels[output].1 } else { &labels[output].0 };
+        rows[row] = xor(result, &h[..LABEL_BYTES]);
+    }
+    rows
+}
+
+pub(super) fn permute_bit(label: &[u8]) -> bool {
+    label[0] & 1 != 0
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_garbled_circuit;
+
+    #[test]
+    fn test_half_of_classic() {
+        use crate::garbled_circuit::classic::Classic;
+
+        let circuit: Circuit = include_str!("../../circuits/adder64.txt").parse().unwrap();
+        assert_eq!(2 * PointAndPermute::estimate_size(&circuit), Classic::estimate_size(&circuit));
+    }
+
+    test_garbled_circuit!(PointAndPermute);
+}
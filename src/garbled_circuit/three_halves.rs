@@ -0,0 +1,225 @@
+use std::convert::TryInto;
+
+use aes::{Aes128, Block, NewBlockCipher};
+use rand::{Rng, RngCore};
+use rand::rngs::OsRng;
+
+use crate::circuit::{Circuit, Gate};
+use crate::garbled_circuit::{GarbledCircuit, garbled_size, Garbling};
+use crate::garbled_circuit::half_gates::{self, GarbledGate, get_lsb, HalfGatesDecoder, HalfGatesEncoder, set_lsb, xor_blocks};
+
+/// Garbled AND gate: three half labels and the encrypted control bits of the four rows.
+type Cipher = ([u64; 3], u8);
+
+/// The "three halves make a whole" scheme of Rosulek and Roy, which keeps free XOR but garbles
+/// an AND gate with 1.5 labels instead of the two of [`HalfGates`](crate::garbled_circuit::half_gates::HalfGates).
+///
+/// Labels are split into a left and a right half. The evaluator with labels `A` and `B` of
+/// colors `i` and `j` hashes `A`, `B` and `A ^ B` to half labels and computes the output as
+///
+/// ```text
+/// C.left  = H(A) ^ H(A ^ B) ^ i * G0 ^ j * G1 ^ R_ij.left(A, B)
+/// C.right = H(B) ^ H(A ^ B) ^ i * G1 ^ j * G2 ^ R_ij.right(A, B)
+/// ```
+///
+/// where `R_ij` is a linear combination of the label halves chosen by two control bits that
+/// only the evaluator of row `ij` can decrypt. The garbler picks the control bits so that the
+/// combinations cancel the free-XOR offset exactly where the AND is true, and randomizes them
+/// so that they are independent of the colors of the false labels.
+pub struct ThreeHalves {
+    garbling: Garbling<GarbledGate<Cipher>>,
+    key: Block,
+}
+
+impl GarbledCircuit<HalfGatesEncoder, HalfGatesDecoder> for ThreeHalves {
+    type Label = Block;
+
+    fn evaluate(&self, input: Vec<Self::Label>) -> Vec<Self::Label> {
+        let block_cipher = Aes128::new(&self.key);
+
+        self.garbling.evaluate(input, |index, gate, cipher, values| match (gate, cipher) {
+            (Gate::And(a, b), GarbledGate::And((g, z))) => {
+                let (i, j) = (get_lsb(&values[a]), get_lsb(&values[b]));
+                let (ha, mask_a) = hash(&block_cipher, &values[a], 3 * index);
+                let (hb, mask_b) = hash(&block_cipher, &values[b], 3 * index + 1);
+                let (hab, _) = hash(&block_cipher, &xor_blocks(&values[a], &values[b]), 3 * index + 2);
+                let row = 2 * i as usize + j as usize;
+                let control = (z >> (2 * row)) & 3 ^ mask_a ^ mask_b;
+
+                let (left, right) = linear(i, j, control, halves(&values[a]), halves(&values[b]));
+                let left = left ^ ha ^ hab ^ if i { g[0] } else { 0 } ^ if j { g[1] } else { 0 };
+                let right = right ^ hb ^ hab ^ if i { g[1] } else { 0 } ^ if j { g[2] } else { 0 };
+                join(left, right)
+            }
+            (Gate::Xor(a, b), GarbledGate::Xor) => xor_blocks(&values[a], &values[b]),
+            (Gate::Eq(_), &GarbledGate::Const(label)) => label,
+            _ => unreachable!(),
+        })
+    }
+
+    fn garble_circuit(circuit: &Circuit) -> (Self, HalfGatesEncoder, HalfGatesDecoder) {
+        let key = {
+            let mut key: Block = Default::default();
+            OsRng.fill_bytes(&mut key);
+            key
+        };
+
+        let block_cipher = Aes128::new(&key);
+
+        // The color of a label is the lowest bit of its left half.
+        let r = {
+            let mut r: Block = Default::default();
+            OsRng.fill_bytes(&mut r);
+            set_lsb(&mut r, true);
+            r
+        };
+        // Labels of AND and XOR outputs are replaced while garbling.
+        let labels = (0..circuit.input_length + circuit.gates.len())
+            .map(|_| {
+                let mut label0: Block = Default::default();
+                OsRng.fill_bytes(&mut label0);
+                (label0, xor_blocks(&label0, &r))
+            })
+            .collect::<Vec<_>>();
+
+        let (garbling, encoding, decoding) = Garbling::new(
+            circuit,
+            labels,
+            |&label| GarbledGate::Const(label),
+            |gate, output, tweak, labels| match gate {
+                Gate::And(a, b) => {
+                    let (label0, cipher) = garble_and(&block_cipher, labels[a], labels[b], &r, 3 * tweak);
+                    labels[output] = (label0, xor_blocks(&label0, &r));
+                    GarbledGate::And(cipher)
+                }
+                Gate::Xor(a, b) => {
+                    labels[output].0 = xor_blocks(&labels[a].0, &labels[b].0);
+                    labels[output].1 = xor_blocks(&labels[output].0, &r);
+                    GarbledGate::Xor
+                }
+                _ => unreachable!(),
+            },
+        );
+        (
+            ThreeHalves { garbling, key },
+            HalfGatesEncoder { inner: encoding, groups: circuit.input_groups.clone() },
+            HalfGatesDecoder {
+                inner: decoding.iter().map(|(w0, _)| get_lsb(w0)).collect(),
+                groups: circuit.output_groups.clone(),
+            },
+        )
+    }
+
+    fn estimate_size(circuit: &Circuit) -> usize {
+        let block = Block::default().len();
+        garbled_size(circuit, block, |_, gate| match gate {
+            // Three half labels and a byte of control bits.
+            Gate::And(_, _) => 3 * block / 2 + 1,
+            _ => 0,
+        })
+    }
+}
+
+/// Control bits of the rows `2 * i + j` for the colors `alpha` and `beta` of the false input
+/// labels, before they are randomized by XORing the same two bits into every row.
+const CONTROL: [[[u8; 4]; 2]; 2] = [
+    [[3, 1, 2, 0], [1, 2, 3, 0]],
+    [[0, 0, 0, 0], [2, 3, 1, 0]],
+];
+
+/// Returns the false output label of an AND gate with input labels `a` and `b`.
+fn garble_and(block_cipher: &Aes128, a: (Block, Block), b: (Block, Block), r: &Block, tweak: usize) -> (Block, Cipher) {
+    let (alpha, beta) = (get_lsb(&a.0), get_lsb(&b.0));
+    // Labels by color instead of by value.
+    let a = if alpha { [a.1, a.0] } else { [a.0, a.1] };
+    let b = if beta { [b.1, b.0] } else { [b.0, b.1] };
+    let ha = [hash(block_cipher, &a[0], tweak), hash(block_cipher, &a[1], tweak)];
+    let hb = [hash(block_cipher, &b[0], tweak + 1), hash(block_cipher, &b[1], tweak + 1)];
+    let hab = [
+        hash(block_cipher, &xor_blocks(&a[0], &b[0]), tweak + 2).0,
+        hash(block_cipher, &xor_blocks(&a[0], &b[1]), tweak + 2).0,
+    ];
+
+    let randomizer = OsRng.gen::<u8>() & 3;
+    let mut z = 0;
+    // What the hashes and label combinations of every row add up to, which must equal the
+    // output label of the row up to the contribution of the half labels G.
+    let mut d = [(0, 0); 4];
+    for row in 0..4 {
+        let (i, j) = (row >> 1, row & 1);
+        let control = CONTROL[alpha as usize][beta as usize][row] ^ randomizer;
+        z |= (control ^ ha[i].1 ^ hb[j].1) << (2 * row);
+
+        let (left, right) = linear(i == 1, j == 1, control, halves(&a[i]), halves(&b[j]));
+        d[row] = (left ^ ha[i].0 ^ hab[i ^ j], right ^ hb[j].0 ^ hab[i ^ j]);
+        if (i == 1) != alpha && (j == 1) != beta {
+            let (r_left, r_right) = halves(r);
+            d[row] = (d[row].0 ^ r_left, d[row].1 ^ r_right);
+        }
+    }
+
+    let c = d[0];
+    let g = [d[2].0 ^ c.0, d[2].1 ^ c.1, d[1].1 ^ c.1];
+    debug_assert_eq!(d[1].0 ^ c.0, g[1]);
+    debug_assert_eq!((d[3].0 ^ c.0, d[3].1 ^ c.1), (g[0] ^ g[1], g[1] ^ g[2]));
+    (join(c.0, c.1), (g, z))
+}
+
+/// Linear combination of the halves of the input labels `a` and `b` for the row with colors
+/// `i` and `j` and two control bits.
+fn linear(i: bool, j: bool, control: u8, (al, ar): (u64, u64), (bl, br): (u64, u64)) -> (u64, u64) {
+    let mut left = if j { al } else { 0 };
+    let mut right = if j { ar } else { 0 } ^ if i { al } else { 0 };
+    if control & 1 != 0 {
+        left ^= al ^ bl ^ br;
+        right ^= al ^ ar ^ br;
+    }
+    if control & 2 != 0 {
+        left ^= al ^ ar ^ br;
+        right ^= ar ^ bl;
+    }
+    (left, right)
+}
+
+/// The hash of [`HalfGates`](crate::garbled_circuit::half_gates::HalfGates), truncated to a
+/// half label and two bits that mask the control bits.
+fn hash(block_cipher: &Aes128, label: &Block, tweak: usize) -> (u64, u8) {
+    let h = half_gates::hash(block_cipher, label, tweak);
+    (halves(&h).0, h[8] & 3)
+}
+
+fn halves(label: &Block) -> (u64, u64) {
+    (u64::from_le_bytes(label[..8].try_into().unwrap()), u64::from_le_bytes(label[8..].try_into().unwrap()))
+}
+
+fn join(left: u64, right: u64) -> Block {
+    let mut block: Block = Default::default();
+    block[..8].copy_from_slice(&left.to_le_bytes());
+    block[8..].copy_from_slice(&right.to_le_bytes());
+    block
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_garbled_circuit;
+
+    #[test]
+    fn test_smaller_than_half_gates() {
+        use crate::garbled_circuit::half_gates::HalfGates;
+
+        let circuit: Circuit = include_str!("../../circuits/aes_128.txt").parse().unwrap();
+        let and_count = circuit.stats().and_count;
+        assert_eq!(HalfGates::estimate_size(&circuit), 32 * and_count);
+        assert_eq!(ThreeHalves::estimate_size(&circuit), 25 * and_count);
+    }
+
+    #[test]
+    fn test_control_bits() {
+        // The control bits of all rows XOR to zero, so the masks, which do as well, hide them.
+        for control in CONTROL.iter().flatten() {
+            assert_eq!(control.iter().fold(0, |x, c| x ^ c), 0);
+        }
+    }
+
+    test_garbled_circuit!(ThreeHalves);
+}
@@ -1,7 +1,7 @@
 pub fn u64_to_bits(mut x: u64) -> Vec<bool> {
     let mut bits = vec![false; 64];
-    for i in 0..64 {
-        bits[i] = x & 1 != 0;
+    for bit in bits.iter_mut() {
+        *bit = x & 1 != 0;
         x >>= 1;
     }
     bits
@@ -29,9 +29,9 @@ pub fn u8_to_bits(mut x: u8) -> Vec<bool> {
 pub fn bits_to_u8(bits: &[bool]) -> u8 {
     assert_eq!(bits.len(), 8);
     let mut x = 0;
-    for i in 0..8 {
+    for &bit in bits {
         x <<= 1;
-        x |= bits[i] as u8;
+        x |= bit as u8;
     }
     x
 }